@@ -1,5 +1,6 @@
 use std::io::{self, Write};
-use christoph_lib::sftp::{SftpClient, ConnectionConfig};
+use christoph_lib::sftp::{known_hosts, SftpClient, SftpError, ConnectionConfig, Secret};
+use ssh2::{KeyboardInteractivePrompt, Prompt};
 
 fn main() {
     println!("╔════════════════════════════════════════╗");
@@ -11,10 +12,14 @@ fn main() {
 
     // Connect
     println!("\n⏳ Connecting to {}:{}...", config.host, config.port);
-    let client = match SftpClient::connect(config) {
+    let client = match connect_trusting(config) {
         Ok(c) => {
             println!("✓ Connected successfully!");
-            println!("  Connection ID: {}\n", c.connection_id());
+            println!("  Connection ID: {}", c.connection_id());
+            if let Some(identity) = c.auth_report().identity() {
+                println!("  Authenticated as: {}", identity);
+            }
+            println!();
             c
         }
         Err(e) => {
@@ -31,25 +36,92 @@ fn main() {
     interactive_shell(client);
 }
 
+struct StdinPrompter;
+
+impl KeyboardInteractivePrompt for StdinPrompter {
+    fn prompt<'a>(&mut self, _username: &str, instructions: &str, prompts: &[Prompt<'a>]) -> Vec<String> {
+        if !instructions.is_empty() {
+            println!("  {}", instructions);
+        }
+        prompts
+            .iter()
+            .map(|p| prompt(p.text.trim_end().trim_end_matches(':'), None))
+            .collect()
+    }
+}
+
+fn connect_trusting(config: ConnectionConfig) -> Result<SftpClient, SftpError> {
+    // Every hop may present an unknown key, so keep asking until none do.
+    loop {
+        let info = match SftpClient::connect(config.clone(), Some(&mut StdinPrompter)) {
+            Err(SftpError::UnknownHost(info)) => {
+                println!("\n⚠ The authenticity of host '{}:{}' can't be established.", info.host, info.port);
+                info
+            }
+            Err(SftpError::HostKeyMismatch(info)) => {
+                println!("\n⚠ WARNING: the host key for '{}:{}' has CHANGED!", info.host, info.port);
+                info
+            }
+            other => return other,
+        };
+
+        println!("  {} key fingerprint is {}", info.key_type, info.fingerprint);
+        if prompt("Trust this host key? (yes/no)", Some("no")) != "yes" {
+            return Err(SftpError::Other { message: "Host key not trusted".to_string() });
+        }
+
+        let known_hosts_path = std::iter::once(&config)
+            .chain(&config.jump_hosts)
+            .find(|hop| hop.host == info.host && hop.port == info.port)
+            .and_then(|hop| hop.known_hosts_path.clone());
+        let path = known_hosts::resolve_known_hosts_path(known_hosts_path.as_deref())?;
+        known_hosts::trust_host_key(&path, &info.host, info.port, &info.fingerprint)?;
+    }
+}
+
 fn get_connection_config() -> ConnectionConfig {
     println!("Enter connection details:\n");
 
     let host = prompt("Host", Some("localhost"));
     let port = prompt_number("Port", 2222);
     let username = prompt("Username", Some("foo"));
-    
+    let mut config = get_auth_config(host, port, username);
+
+    // Bastions are entered outermost first
+    while let Some(jump) = prompt_optional("Jump host as user@host:port (optional)") {
+        let (username, host_port) = jump.split_once('@').unwrap_or(("foo", jump.as_str()));
+        let (host, port) = match host_port.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().unwrap_or(22)),
+            None => (host_port, 22),
+        };
+        println!("\nJump host {}@{}:{}", username, host, port);
+        config
+            .jump_hosts
+            .push(get_auth_config(host.to_string(), port, username.to_string()));
+    }
+
+    config
+}
+
+fn get_auth_config(host: String, port: u16, username: String) -> ConnectionConfig {
     println!("\nAuthentication method:");
     println!("  1. Password");
     println!("  2. Private Key");
-    let auth_method = prompt("Choose (1 or 2)", Some("1"));
-
-    let (password, private_key_path, passphrase) = if auth_method == "2" {
-        let key_path = prompt("Private key path", None);
-        let passphrase = prompt_optional("Passphrase (optional)");
-        (None, Some(key_path), passphrase)
-    } else {
-        let password = prompt("Password", Some("pass"));
-        (Some(password), None, None)
+    println!("  3. SSH Agent");
+    println!("  4. Keyboard-interactive only");
+    let auth_method = prompt("Choose (1-4)", Some("1"));
+
+    let (password, private_key_path, passphrase) = match auth_method.as_str() {
+        "2" => {
+            let key_path = prompt("Private key path", None);
+            let passphrase = prompt_optional("Passphrase (optional)").map(Secret::from);
+            (None, Some(key_path), passphrase)
+        }
+        "3" | "4" => (None, None, None),
+        _ => {
+            let password = prompt("Password", Some("pass"));
+            (Some(Secret::from(password)), None, None)
+        }
     };
 
     ConnectionConfig {
@@ -59,6 +131,8 @@ fn get_connection_config() -> ConnectionConfig {
         password,
         private_key_path,
         passphrase,
+        use_agent: auth_method == "3",
+        ..Default::default()
     }
 }
 
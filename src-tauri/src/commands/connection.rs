@@ -1,36 +1,85 @@
-use crate::{sftp::{ConnectionConfig, SftpClient}, state::connection_pool::{CONNECTION_POOL}};
+use tauri::Window;
+
+use crate::{sftp::{known_hosts, prompt::{self, WindowPrompter}, ssh_config, ConnectionConfig, ConnectionInfo, SftpClient, SftpError}, state::connection_pool::{ConnectionDetails, CONNECTION_POOL}};
 
 
 #[tauri::command]
 pub async fn connect_sftp(
-    host: String,
-    port: u16,
-    username: String,
-    password: Option<String>,
-    passphrase: Option<String>,
-    private_key_path: Option<String>,
-) -> Result<String, String> {
-    let config = ConnectionConfig {
-        host,
-        port,
-        username,
-        password,
-        passphrase,
-        private_key_path,
+    config: ConnectionConfig,
+    window: Window,
+) -> Result<ConnectionInfo, SftpError> {
+    open_connection(config, None, window).await
+}
+
+pub async fn open_connection(
+    config: ConnectionConfig,
+    profile_id: Option<String>,
+    window: Window,
+) -> Result<ConnectionInfo, SftpError> {
+    let details = ConnectionDetails {
+        host: config.host.clone(),
+        port: config.port,
+        username: config.username.clone(),
+        profile_id,
     };
+
+    // Runs on a blocking thread: keyboard-interactive auth waits on the user.
+    let prompt_window = window.clone();
+    let client = tokio::task::spawn_blocking(move || {
+        let host = config.host.clone();
+        let mut prompter = WindowPrompter::new(prompt_window, &host);
+        let result = SftpClient::connect(config, Some(&mut prompter));
+        if result.is_err() && prompter.cancelled() {
+            return Err(SftpError::cancelled("authenticate", &host));
+        }
+        result
+    })
+    .await
+    .map_err(|e| format!("Connection task failed: {}", e))??;
+    let info = ConnectionInfo {
+        connection_id: client.connection_id().to_string(),
+        identity: client.auth_report().identity().map(str::to_string),
+        auth: client.auth_report().clone(),
+    };
+    CONNECTION_POOL.add(info.connection_id.clone(), client, details, window);
     
-    let client = SftpClient::connect(config)?;
-    let connection_id = client.connection_id().to_string();
-    CONNECTION_POOL.add(connection_id.clone(), client);
-    
-    Ok(connection_id)
+    Ok(info)
 }
 
 
 #[tauri::command]
-pub async fn disconnect_sftp(connection_id: String) -> Result<(), String> {
+pub async fn disconnect_sftp(connection_id: String) -> Result<(), SftpError> {
     CONNECTION_POOL
         .remove(&connection_id)
-        .ok_or_else(|| format!("Connection {} not found", connection_id))?;
+        .ok_or(SftpError::ConnectionNotFound { connection_id })?;
     Ok(())
+}
+
+#[tauri::command]
+pub async fn trust_host_key(
+    host: String,
+    port: u16,
+    fingerprint: String,
+    known_hosts_path: Option<String>,
+) -> Result<(), SftpError> {
+    let path = known_hosts::resolve_known_hosts_path(known_hosts_path.as_deref())?;
+    Ok(known_hosts::trust_host_key(&path, &host, port, &fingerprint)?)
+}
+
+#[tauri::command]
+pub async fn respond_auth_prompt(
+    prompt_id: String,
+    responses: Option<Vec<String>>,
+) -> Result<(), SftpError> {
+    Ok(prompt::respond_to_prompt(&prompt_id, responses)?)
+}
+
+#[tauri::command]
+pub async fn list_ssh_hosts() -> Result<Vec<String>, SftpError> {
+    Ok(ssh_config::list_host_aliases()?)
+}
+
+#[tauri::command]
+pub async fn resolve_ssh_host(alias: String) -> Result<ConnectionConfig, SftpError> {
+    Ok(ssh_config::resolve_host(&alias)?)
 }
\ No newline at end of file
@@ -0,0 +1,52 @@
+use std::path::Path;
+
+use tauri::Window;
+use uuid::Uuid;
+
+use crate::commands::operations::enqueue;
+use crate::sftp::SftpError;
+use crate::state::history::{ExportFormat, HistoryPage, HistoryQuery, TRANSFER_HISTORY};
+
+#[tauri::command]
+pub async fn query_transfer_history(query: Option<HistoryQuery>) -> Result<HistoryPage, SftpError> {
+    TRANSFER_HISTORY.query(&query.unwrap_or_default())
+}
+
+/// Writes the entries matching `query` to `path` and returns how many
+/// there were.
+#[tauri::command]
+pub async fn export_transfer_history(
+    path: String,
+    format: ExportFormat,
+    query: Option<HistoryQuery>,
+) -> Result<usize, SftpError> {
+    TRANSFER_HISTORY.export(&query.unwrap_or_default(), format, Path::new(&path))
+}
+
+#[tauri::command]
+pub async fn clear_transfer_history() -> Result<(), SftpError> {
+    TRANSFER_HISTORY.clear()
+}
+
+/// Queues a fresh transfer of the same paths with the same options. The
+/// original connections are usually gone by now, so `connection_id` (and
+/// `target_connection_id` for copies) pick the ones to use.
+#[tauri::command]
+pub async fn rerun_transfer(
+    entry_id: String,
+    connection_id: Option<String>,
+    target_connection_id: Option<String>,
+    priority: Option<i32>,
+    window: Window,
+) -> Result<String, SftpError> {
+    let mut record = TRANSFER_HISTORY.get(&entry_id)?.record;
+    record.transfer_id = Uuid::new_v4().to_string();
+    record.resolved.clear();
+    if let Some(connection_id) = connection_id {
+        record.connection_id = connection_id;
+    }
+    if record.target_connection_id.is_some() && target_connection_id.is_some() {
+        record.target_connection_id = target_connection_id;
+    }
+    enqueue(record, false, priority, window)
+}
@@ -1,2 +1,6 @@
 pub mod connection;
+pub mod history;
 pub mod operations;
+pub mod profiles;
+pub mod transfers;
+pub mod vault;
@@ -1,21 +1,17 @@
 use tauri::Window;
 
 use crate::state::connection_pool::CONNECTION_POOL;
-use crate::sftp::{cancel_transfer, start_download, start_upload};
+use crate::state::transfer_queue::TRANSFER_QUEUE;
+use crate::sftp::checksum::{self, Checksum};
+use crate::sftp::transfer::{TransferDirection, TransferOptions, TransferRecord};
+use crate::sftp::SftpError;
 
 #[tauri::command]
 pub async fn list_directory(
     connection_id: String,
     path: String,
-) -> Result<Vec<crate::sftp::FileInfo>, String> {
-    let client_arc = CONNECTION_POOL
-        .get(&connection_id)
-        .ok_or_else(|| "Connection not found".to_string())?;
-    
-    let client = client_arc.lock()
-        .map_err(|e| format!("Failed to lock client: {}", e))?;
-    
-    client.list_directory(&path)
+) -> Result<Vec<crate::sftp::FileInfo>, SftpError> {
+    CONNECTION_POOL.run(&connection_id, |client| client.list_directory(&path))
 }
 
 #[tauri::command]
@@ -23,13 +19,18 @@ pub async fn upload_file(
     connection_id: String,
     local_path: String,
     remote_path: String,
+    priority: Option<i32>,
+    options: Option<TransferOptions>,
     window: Window,
-) -> Result<String, String> {
-    let client_arc = CONNECTION_POOL
-        .get(&connection_id)
-        .ok_or_else(|| "Connection not found".to_string())?;
-    
-    start_upload(local_path, remote_path, window, client_arc)
+) -> Result<String, SftpError> {
+    let record = TransferRecord::new(
+        connection_id,
+        TransferDirection::Upload,
+        local_path,
+        remote_path,
+        options.unwrap_or_default(),
+    );
+    enqueue(record, false, priority, window)
 }
 
 #[tauri::command]
@@ -37,18 +38,63 @@ pub async fn download_file(
     connection_id: String,
     remote_path: String,
     local_path: String,
+    priority: Option<i32>,
+    options: Option<TransferOptions>,
     window: Window,
-) -> Result<String, String> {
-    let client_arc = CONNECTION_POOL
-        .get(&connection_id)
-        .ok_or_else(|| "Connection not found".to_string())?;
-    
-    start_download(remote_path, local_path, window, client_arc)
+) -> Result<String, SftpError> {
+    let record = TransferRecord::new(
+        connection_id,
+        TransferDirection::Download,
+        local_path,
+        remote_path,
+        options.unwrap_or_default(),
+    );
+    enqueue(record, false, priority, window)
 }
 
+/// Queues a failed or cancelled transfer again from the record sent with
+/// its `transfer_error` or `transfer_cancelled` event, resuming partial files.
+/// It keeps its transfer ID, so this fails while the original is still queued.
 #[tauri::command]
-pub async fn cancel_file_transfer(transfer_id: String) -> Result<(), String> {
-    cancel_transfer(&transfer_id)
+pub async fn retry_transfer(
+    record: TransferRecord,
+    priority: Option<i32>,
+    window: Window,
+) -> Result<String, SftpError> {
+    enqueue(record, true, priority, window)
+}
+
+/// Copies a file or directory from one connection to another, streaming it
+/// through memory rather than local disk.
+#[tauri::command]
+pub async fn copy_between_connections(
+    source_connection_id: String,
+    source_path: String,
+    target_connection_id: String,
+    target_path: String,
+    priority: Option<i32>,
+    options: Option<TransferOptions>,
+    window: Window,
+) -> Result<String, SftpError> {
+    let record = TransferRecord::copy(
+        source_connection_id,
+        source_path,
+        target_connection_id,
+        target_path,
+        options.unwrap_or_default(),
+    );
+    enqueue(record, false, priority, window)
+}
+
+#[tauri::command]
+pub async fn cancel_file_transfer(transfer_id: String) -> Result<(), SftpError> {
+    TRANSFER_QUEUE.cancel(&transfer_id)
+}
+
+/// SHA-256 of a remote file, computed by the server when it allows it.
+#[tauri::command]
+pub async fn checksum_remote_file(connection_id: String, path: String) -> Result<Checksum, SftpError> {
+    CONNECTION_POOL.run(&connection_id, |client| checksum::remote_checksum(client, &path))
 }
 
 #[tauri::command]
@@ -56,30 +102,16 @@ pub async fn delete_file(
     connection_id: String,
     path: String,
     is_dir: bool,
-) -> Result<(), String> {
-    let client_arc = CONNECTION_POOL
-        .get(&connection_id)
-        .ok_or_else(|| "Connection not found".to_string())?;
-    
-    let client = client_arc.lock()
-        .map_err(|e| format!("Failed to lock client: {}", e))?;
-    
-    client.delete(&path, is_dir)
+) -> Result<(), SftpError> {
+    CONNECTION_POOL.run(&connection_id, |client| client.delete(&path, is_dir))
 }
 
 #[tauri::command]
 pub async fn create_directory(
     connection_id: String,
     path: String,
-) -> Result<(), String> {
-    let client_arc = CONNECTION_POOL
-        .get(&connection_id)
-        .ok_or_else(|| "Connection not found".to_string())?;
-    
-    let client = client_arc.lock()
-        .map_err(|e| format!("Failed to lock client: {}", e))?;
-    
-    client.create_directory(&path)
+) -> Result<(), SftpError> {
+    CONNECTION_POOL.run(&connection_id, |client| client.create_directory(&path))
 }
 
 #[tauri::command]
@@ -87,13 +119,40 @@ pub async fn rename_file(
     connection_id: String,
     old_path: String,
     new_path: String,
-) -> Result<(), String> {
-    let client_arc = CONNECTION_POOL
-        .get(&connection_id)
-        .ok_or_else(|| "Connection not found".to_string())?;
-    
-    let client = client_arc.lock()
-        .map_err(|e| format!("Failed to lock client: {}", e))?;
-    
-    client.rename(&old_path, &new_path)
-}
\ No newline at end of file
+) -> Result<(), SftpError> {
+    CONNECTION_POOL.run(&connection_id, |client| client.rename(&old_path, &new_path))
+}
+
+/// Checks that the record's connections exist, and for copies that they
+/// differ, before queueing it. Every way of starting a transfer goes
+/// through here, including retries and re-runs from the history.
+pub fn enqueue(
+    record: TransferRecord,
+    resume: bool,
+    priority: Option<i32>,
+    window: Window,
+) -> Result<String, SftpError> {
+    if CONNECTION_POOL.get(&record.connection_id).is_none() {
+        return Err(SftpError::ConnectionNotFound {
+            connection_id: record.connection_id,
+        });
+    }
+
+    if record.direction == TransferDirection::Copy {
+        let Some(target_connection_id) = &record.target_connection_id else {
+            return Err(SftpError::from("Copy has no target connection".to_string()));
+        };
+        if *target_connection_id == record.connection_id {
+            return Err(SftpError::from(
+                "Source and target must be different connections".to_string(),
+            ));
+        }
+        if CONNECTION_POOL.get(target_connection_id).is_none() {
+            return Err(SftpError::ConnectionNotFound {
+                connection_id: target_connection_id.clone(),
+            });
+        }
+    }
+
+    TRANSFER_QUEUE.enqueue(record, resume, priority.unwrap_or(0), window)
+}
@@ -0,0 +1,77 @@
+use tauri::Window;
+
+use crate::commands::connection::open_connection;
+use crate::sftp::{ConnectionConfig, ConnectionInfo, Secret, SftpError};
+use crate::state::profiles::{ConnectionProfile, JumpHostSecrets, PROFILE_STORE};
+use crate::state::vault::VAULT;
+
+#[tauri::command]
+pub async fn list_profiles() -> Result<Vec<ConnectionProfile>, SftpError> {
+    Ok(PROFILE_STORE.list()?)
+}
+
+#[tauri::command]
+pub async fn create_profile(
+    name: String,
+    config: ConnectionConfig,
+    password_secret_id: Option<String>,
+    passphrase_secret_id: Option<String>,
+    jump_host_secrets: Option<Vec<JumpHostSecrets>>,
+) -> Result<ConnectionProfile, SftpError> {
+    Ok(PROFILE_STORE.create(
+        name,
+        config,
+        password_secret_id,
+        passphrase_secret_id,
+        jump_host_secrets.unwrap_or_default(),
+    )?)
+}
+
+#[tauri::command]
+pub async fn update_profile(profile: ConnectionProfile) -> Result<ConnectionProfile, SftpError> {
+    Ok(PROFILE_STORE.update(profile)?)
+}
+
+#[tauri::command]
+pub async fn delete_profile(profile_id: String) -> Result<(), SftpError> {
+    Ok(PROFILE_STORE.delete(&profile_id)?)
+}
+
+#[tauri::command]
+pub async fn duplicate_profile(profile_id: String) -> Result<ConnectionProfile, SftpError> {
+    Ok(PROFILE_STORE.duplicate(&profile_id)?)
+}
+
+#[tauri::command]
+pub async fn connect_profile(
+    profile_id: String,
+    password: Option<Secret>,
+    passphrase: Option<Secret>,
+    window: Window,
+) -> Result<ConnectionInfo, SftpError> {
+    let profile = PROFILE_STORE.get(&profile_id)?;
+    let mut config = profile.config;
+
+    // Secrets given at connect time win over the ones saved in the vault.
+    config.password = match (password, &profile.password_secret_id) {
+        (Some(password), _) => Some(password),
+        (None, Some(secret_id)) => Some(VAULT.get(secret_id)?),
+        (None, None) => None,
+    };
+    config.passphrase = match (passphrase, &profile.passphrase_secret_id) {
+        (Some(passphrase), _) => Some(passphrase),
+        (None, Some(secret_id)) => Some(VAULT.get(secret_id)?),
+        (None, None) => None,
+    };
+    // Jump hosts only ever get their secrets from the vault.
+    for (jump, secrets) in config.jump_hosts.iter_mut().zip(&profile.jump_host_secrets) {
+        if let Some(secret_id) = &secrets.password_secret_id {
+            jump.password = Some(VAULT.get(secret_id)?);
+        }
+        if let Some(secret_id) = &secrets.passphrase_secret_id {
+            jump.passphrase = Some(VAULT.get(secret_id)?);
+        }
+    }
+
+    open_connection(config, Some(profile_id), window).await
+}
@@ -0,0 +1,75 @@
+use crate::sftp::conflict::{self, ConflictAnswer};
+use crate::sftp::throttle::GLOBAL_RATE_LIMIT;
+use crate::sftp::SftpError;
+use crate::state::transfer_queue::{QueueEntry, QueueLimits, TRANSFER_QUEUE};
+
+#[tauri::command]
+pub async fn list_transfer_queue() -> Vec<QueueEntry> {
+    TRANSFER_QUEUE.list()
+}
+
+/// Holds a queued job back, or stops a running one between chunks.
+#[tauri::command]
+pub async fn pause_transfer(transfer_id: String) -> Result<(), SftpError> {
+    TRANSFER_QUEUE.pause(&transfer_id)
+}
+
+#[tauri::command]
+pub async fn resume_transfer(transfer_id: String) -> Result<(), SftpError> {
+    TRANSFER_QUEUE.resume(&transfer_id)
+}
+
+#[tauri::command]
+pub async fn set_transfer_priority(transfer_id: String, priority: i32) -> Result<(), SftpError> {
+    TRANSFER_QUEUE.set_priority(&transfer_id, priority)
+}
+
+#[tauri::command]
+pub async fn move_transfer(transfer_id: String, position: usize) -> Result<(), SftpError> {
+    TRANSFER_QUEUE.move_to(&transfer_id, position)
+}
+
+/// Answers a `transfer_conflict` prompt; `None` cancels the transfer.
+#[tauri::command]
+pub async fn respond_transfer_conflict(
+    prompt_id: String,
+    answer: Option<ConflictAnswer>,
+) -> Result<(), SftpError> {
+    conflict::respond_to_conflict(&prompt_id, answer)
+}
+
+/// Drops every transfer that hasn't started yet, or only those for
+/// `connection_id`. Returns how many were dropped.
+#[tauri::command]
+pub async fn clear_transfer_queue(connection_id: Option<String>) -> usize {
+    TRANSFER_QUEUE.clear(connection_id.as_deref())
+}
+
+#[tauri::command]
+pub async fn get_transfer_limits() -> QueueLimits {
+    TRANSFER_QUEUE.limits()
+}
+
+#[tauri::command]
+pub async fn set_transfer_limits(limits: QueueLimits) {
+    TRANSFER_QUEUE.set_limits(limits)
+}
+
+/// Limits are in bytes per second; `None` or 0 removes the limit.
+#[tauri::command]
+pub async fn set_transfer_rate_limit(
+    transfer_id: String,
+    bytes_per_second: Option<u64>,
+) -> Result<(), SftpError> {
+    TRANSFER_QUEUE.set_rate_limit(&transfer_id, bytes_per_second)
+}
+
+#[tauri::command]
+pub async fn get_global_rate_limit() -> Option<u64> {
+    GLOBAL_RATE_LIMIT.rate()
+}
+
+#[tauri::command]
+pub async fn set_global_rate_limit(bytes_per_second: Option<u64>) {
+    GLOBAL_RATE_LIMIT.set_rate(bytes_per_second)
+}
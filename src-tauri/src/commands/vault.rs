@@ -0,0 +1,56 @@
+use crate::sftp::{Secret, SftpError};
+use crate::state::vault::{VaultEntryInfo, VaultStatus, VAULT};
+
+#[tauri::command]
+pub async fn vault_status() -> Result<VaultStatus, SftpError> {
+    Ok(VAULT.status()?)
+}
+
+#[tauri::command]
+pub async fn create_vault(master_password: Secret) -> Result<(), SftpError> {
+    tokio::task::spawn_blocking(move || VAULT.create(master_password.expose()))
+        .await
+        .map_err(|e| format!("Vault task failed: {}", e))??;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn unlock_vault(master_password: Secret) -> Result<(), SftpError> {
+    // Key derivation is deliberately slow, keep it off the async runtime.
+    tokio::task::spawn_blocking(move || VAULT.unlock(master_password.expose()))
+        .await
+        .map_err(|e| format!("Vault task failed: {}", e))??;
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn lock_vault() -> Result<(), SftpError> {
+    VAULT.lock();
+    Ok(())
+}
+
+/// `seconds` of 0 disables auto-lock.
+#[tauri::command]
+pub async fn set_vault_auto_lock(seconds: u64) -> Result<(), SftpError> {
+    Ok(VAULT.set_auto_lock(seconds)?)
+}
+
+#[tauri::command]
+pub async fn list_vault_entries() -> Result<Vec<VaultEntryInfo>, SftpError> {
+    Ok(VAULT.list()?)
+}
+
+#[tauri::command]
+pub async fn store_vault_secret(label: String, secret: Secret) -> Result<String, SftpError> {
+    Ok(VAULT.store(label, secret.expose())?)
+}
+
+#[tauri::command]
+pub async fn update_vault_secret(entry_id: String, secret: Secret) -> Result<(), SftpError> {
+    Ok(VAULT.update(&entry_id, secret.expose())?)
+}
+
+#[tauri::command]
+pub async fn delete_vault_entry(entry_id: String) -> Result<(), SftpError> {
+    Ok(VAULT.delete(&entry_id)?)
+}
@@ -11,14 +11,56 @@ fn main() {
             // Connection commands
             commands::connection::connect_sftp,
             commands::connection::disconnect_sftp,
+            commands::connection::trust_host_key,
+            commands::connection::respond_auth_prompt,
+            commands::connection::list_ssh_hosts,
+            commands::connection::resolve_ssh_host,
+            // Profile commands
+            commands::profiles::list_profiles,
+            commands::profiles::create_profile,
+            commands::profiles::update_profile,
+            commands::profiles::delete_profile,
+            commands::profiles::duplicate_profile,
+            commands::profiles::connect_profile,
+            // Vault commands
+            commands::vault::vault_status,
+            commands::vault::create_vault,
+            commands::vault::unlock_vault,
+            commands::vault::lock_vault,
+            commands::vault::set_vault_auto_lock,
+            commands::vault::list_vault_entries,
+            commands::vault::store_vault_secret,
+            commands::vault::update_vault_secret,
+            commands::vault::delete_vault_entry,
             // File operation commands
             commands::operations::list_directory,
             commands::operations::upload_file,
             commands::operations::download_file,
+            commands::operations::retry_transfer,
+            commands::operations::copy_between_connections,
             commands::operations::cancel_file_transfer,
+            commands::operations::checksum_remote_file,
             commands::operations::delete_file,
             commands::operations::create_directory, 
             commands::operations::rename_file,
+            // Transfer queue commands
+            commands::transfers::list_transfer_queue,
+            commands::transfers::pause_transfer,
+            commands::transfers::resume_transfer,
+            commands::transfers::set_transfer_priority,
+            commands::transfers::move_transfer,
+            commands::transfers::clear_transfer_queue,
+            commands::transfers::respond_transfer_conflict,
+            commands::transfers::get_transfer_limits,
+            commands::transfers::set_transfer_limits,
+            commands::transfers::set_transfer_rate_limit,
+            commands::transfers::get_global_rate_limit,
+            commands::transfers::set_global_rate_limit,
+            // Transfer history commands
+            commands::history::query_transfer_history,
+            commands::history::export_transfer_history,
+            commands::history::clear_transfer_history,
+            commands::history::rerun_transfer,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
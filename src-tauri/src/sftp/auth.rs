@@ -0,0 +1,229 @@
+use std::path::PathBuf;
+
+use ssh2::{KeyboardInteractivePrompt, Prompt, Session};
+
+use crate::sftp::types::{AuthAttempt, AuthMethod, AuthOutcome, AuthReport, Secret};
+use crate::sftp::utils::{format_fingerprint, is_timeout};
+use crate::sftp::ConnectionConfig;
+
+const DEFAULT_IDENTITY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+/// Works through the configured authentication methods in order, skipping
+/// the ones the server doesn't offer, until the session is authenticated.
+/// Every attempt is recorded in the returned report.
+pub fn authenticate(
+    session: &Session,
+    config: &ConnectionConfig,
+    mut prompter: Option<&mut dyn KeyboardInteractivePrompt>,
+) -> AuthReport {
+    let username = config.username.as_str();
+    let mut report = AuthReport {
+        host: config.host.clone(),
+        server_methods: server_auth_methods(session, username),
+        attempts: Vec::new(),
+        timed_out: false,
+    };
+
+    // libssh2 probes with the "none" method; some servers accept it outright.
+    if session.authenticated() {
+        return report;
+    }
+
+    for method in config.auth_chain() {
+        if !report.server_methods.iter().any(|m| m == method.protocol_name()) {
+            report.attempts.push(AuthAttempt::skipped(method, "Not offered by server"));
+            continue;
+        }
+
+        match method {
+            AuthMethod::Agent => try_agent(session, username, &mut report),
+            AuthMethod::PublicKey => try_key_files(session, config, &mut report),
+            AuthMethod::Password => match &config.password {
+                Some(password) => {
+                    let result = session.userauth_password(username, password.expose());
+                    record(session, username, &mut report, method, None, result);
+                }
+                None => report
+                    .attempts
+                    .push(AuthAttempt::skipped(method, "No password configured")),
+            },
+            AuthMethod::KeyboardInteractive => match prompter.as_deref_mut() {
+                Some(prompter) => {
+                    // libssh2 measures the timeout across the whole call, which
+                    // here includes however long the user takes to answer.
+                    let timeout = session.timeout();
+                    session.set_timeout(0);
+                    let result =
+                        session.userauth_keyboard_interactive(username, &mut DynPrompter(prompter));
+                    session.set_timeout(timeout);
+                    record(session, username, &mut report, method, None, result);
+                }
+                None => report
+                    .attempts
+                    .push(AuthAttempt::skipped(method, "No prompt handler available")),
+            },
+        }
+
+        if session.authenticated() || report.timed_out {
+            break;
+        }
+    }
+
+    report
+}
+
+/// Records the outcome of one attempt. A rejected attempt after which the
+/// server narrows its list of allowed methods is reported as a partial
+/// success, e.g. a public key that must be followed by an OTP.
+fn record(
+    session: &Session,
+    username: &str,
+    report: &mut AuthReport,
+    method: AuthMethod,
+    identity: Option<String>,
+    result: Result<(), ssh2::Error>,
+) {
+    let (outcome, error) = match result {
+        Ok(()) if session.authenticated() => (AuthOutcome::Success, None),
+        Ok(()) => (AuthOutcome::Failed, Some("Not accepted".to_string())),
+        Err(e) if is_timeout(&e) => {
+            report.timed_out = true;
+            (AuthOutcome::Failed, Some(e.to_string()))
+        }
+        Err(e) => {
+            let methods = server_auth_methods(session, username);
+            if !methods.is_empty() && methods != report.server_methods {
+                report.server_methods = methods;
+                (AuthOutcome::Partial, None)
+            } else {
+                (AuthOutcome::Failed, Some(e.to_string()))
+            }
+        }
+    };
+
+    report.attempts.push(AuthAttempt {
+        method,
+        identity,
+        outcome,
+        error,
+    });
+}
+
+fn try_agent(session: &Session, username: &str, report: &mut AuthReport) {
+    let identities = session.agent().and_then(|mut agent| {
+        agent.connect()?;
+        agent.list_identities()?;
+        let identities = agent.identities()?;
+        Ok((agent, identities))
+    });
+
+    let (mut agent, identities) = match identities {
+        Ok(found) => found,
+        Err(e) => {
+            report.attempts.push(AuthAttempt::failed(
+                AuthMethod::Agent,
+                None,
+                format!("SSH agent unavailable: {}", e),
+            ));
+            return;
+        }
+    };
+
+    if identities.is_empty() {
+        report.attempts.push(AuthAttempt::failed(
+            AuthMethod::Agent,
+            None,
+            "SSH agent has no identities".to_string(),
+        ));
+    }
+
+    for identity in &identities {
+        let description = describe_identity(identity.comment(), identity.blob());
+        let result = agent.userauth(username, identity);
+        record(session, username, report, AuthMethod::Agent, Some(description), result);
+
+        if session.authenticated() || report.timed_out || last_was_partial(report) {
+            break;
+        }
+    }
+
+    agent.disconnect().ok();
+}
+
+fn try_key_files(session: &Session, config: &ConnectionConfig, report: &mut AuthReport) {
+    let key_files = match &config.private_key_path {
+        Some(path) => vec![PathBuf::from(path)],
+        None => default_identity_files(),
+    };
+
+    if key_files.is_empty() {
+        report.attempts.push(AuthAttempt::skipped(
+            AuthMethod::PublicKey,
+            "No private key configured",
+        ));
+        return;
+    }
+
+    for key_file in key_files {
+        let result = session.userauth_pubkey_file(
+            &config.username,
+            None,
+            &key_file,
+            config.passphrase.as_ref().map(Secret::expose),
+        );
+        let identity = key_file.to_string_lossy().to_string();
+        record(session, &config.username, report, AuthMethod::PublicKey, Some(identity), result);
+
+        if session.authenticated() || report.timed_out || last_was_partial(report) {
+            break;
+        }
+    }
+}
+
+fn last_was_partial(report: &AuthReport) -> bool {
+    report
+        .attempts
+        .last()
+        .is_some_and(|attempt| attempt.outcome == AuthOutcome::Partial)
+}
+
+fn default_identity_files() -> Vec<PathBuf> {
+    let Some(home) = dirs::home_dir() else {
+        return Vec::new();
+    };
+
+    DEFAULT_IDENTITY_FILES
+        .iter()
+        .map(|name| home.join(".ssh").join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+pub fn server_auth_methods(session: &Session, username: &str) -> Vec<String> {
+    session
+        .auth_methods(username)
+        .map(|methods| methods.split(',').map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+fn describe_identity(comment: &str, blob: &[u8]) -> String {
+    if comment.is_empty() {
+        format_fingerprint(blob)
+    } else {
+        format!("{} ({})", comment, format_fingerprint(blob))
+    }
+}
+
+// ssh2 wants a sized prompter, so wrap the trait object.
+struct DynPrompter<'p>(&'p mut dyn KeyboardInteractivePrompt);
+
+impl KeyboardInteractivePrompt for DynPrompter<'_> {
+    fn prompt<'a>(
+        &mut self,
+        username: &str,
+        instructions: &str,
+        prompts: &[Prompt<'a>],
+    ) -> Vec<String> {
+        self.0.prompt(username, instructions, prompts)
+    }
+}
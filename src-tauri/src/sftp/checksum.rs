@@ -0,0 +1,108 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::sftp::utils::shell_quote;
+use crate::sftp::{SftpClient, SftpError};
+
+const ALGORITHM: &str = "sha256";
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumMethod {
+    /// Hashed on this machine.
+    Local,
+    /// Hashed by the server running `sha256sum`.
+    RemoteCommand,
+    /// Read over SFTP and hashed here, for servers that don't allow exec.
+    Stream,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Checksum {
+    pub algorithm: &'static str,
+    pub digest: String,
+    pub method: ChecksumMethod,
+}
+
+/// For copies between connections, `local` is the source's checksum and
+/// `remote` the target's.
+#[derive(Serialize, Clone, Debug)]
+pub struct Verification {
+    pub matched: bool,
+    pub local: Checksum,
+    pub remote: Checksum,
+}
+
+pub fn local_checksum(path: &str) -> Result<Checksum, SftpError> {
+    let file = fs::File::open(path).map_err(|e| SftpError::local("checksum", path, e))?;
+    let digest = hash_reader(file).map_err(|e| SftpError::local("checksum", path, e))?;
+
+    Ok(Checksum {
+        algorithm: ALGORITHM,
+        digest,
+        method: ChecksumMethod::Local,
+    })
+}
+
+/// Lets the server hash the file when it allows running `sha256sum`, since
+/// that avoids reading the whole file back. libssh2 has no way to send the
+/// `check-file` SFTP extension, so otherwise the file is streamed and hashed
+/// here.
+pub fn remote_checksum(client: &SftpClient, path: &str) -> Result<Checksum, SftpError> {
+    if let Some(digest) = exec_sha256(client, path) {
+        return Ok(Checksum {
+            algorithm: ALGORITHM,
+            digest,
+            method: ChecksumMethod::RemoteCommand,
+        });
+    }
+
+    let file = client
+        .sftp()
+        .open(Path::new(path))
+        .map_err(|e| client.remote_error("checksum", path, e))?;
+    let digest = hash_reader(file).map_err(|e| client.remote_io_error("checksum", path, e))?;
+
+    Ok(Checksum {
+        algorithm: ALGORITHM,
+        digest,
+        method: ChecksumMethod::Stream,
+    })
+}
+
+pub fn verify(client: &SftpClient, local_path: &str, remote_path: &str) -> Result<Verification, SftpError> {
+    let local = local_checksum(local_path)?;
+    let remote = remote_checksum(client, remote_path)?;
+
+    Ok(Verification {
+        matched: local.digest == remote.digest,
+        local,
+        remote,
+    })
+}
+
+fn exec_sha256(client: &SftpClient, path: &str) -> Option<String> {
+    let output = client.exec(&format!("sha256sum {}", shell_quote(path)))?;
+    let digest = output.split_whitespace().next()?;
+    if digest.len() != 64 || !digest.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(digest.to_lowercase())
+}
+
+fn hash_reader(mut reader: impl Read) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 32 * 1024];
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
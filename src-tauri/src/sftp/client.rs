@@ -1,75 +1,71 @@
 // sftp_client.rs
-use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::{net::TcpStream, path::Path};
-use std::io::{Read, Write};
-
-use once_cell::sync::Lazy;
-use ssh2::{Session, Sftp};
-use tauri::{Emitter, Window};
-use uuid::Uuid;
+use std::{net::{TcpStream, ToSocketAddrs}, path::Path};
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
 
-use crate::sftp::utils::format_permissions;
-use crate::sftp::{ConnectionConfig, FileInfo};
+use ssh2::{KeyboardInteractivePrompt, Session, Sftp};
+use uuid::Uuid;
 
-static TRANSFER_CANCEL_MAP: Lazy<Mutex<HashMap<String, Arc<AtomicBool>>>> =
-    Lazy::new(|| Mutex::new(HashMap::new()));
+use crate::sftp::auth;
+use crate::sftp::known_hosts::{self, HostKeyStatus};
+use crate::sftp::utils::{format_permissions, is_timeout};
+use crate::sftp::tunnel::JumpTunnel;
+use crate::sftp::types::AuthReport;
+use crate::sftp::{SftpError, ConnectionConfig, FileInfo};
 
 pub struct SftpClient {
-    #[allow(dead_code)]
     session: Session,
     sftp: Sftp,
-    #[allow(dead_code)]
+    // Only keeps passwords and passphrases when `auto_reconnect` needs them.
     config: ConnectionConfig,
     connection_id: String,
+    auth_report: AuthReport,
+    // Declared last so the session above is torn down before its tunnels.
+    _tunnels: Vec<JumpTunnel>,
 }
 
 impl SftpClient {
 
-    pub fn connect(config: ConnectionConfig) -> Result<Self, String> {
+    pub fn connect(
+        config: ConnectionConfig,
+        mut prompter: Option<&mut dyn KeyboardInteractivePrompt>,
+    ) -> Result<Self, SftpError> {
         let connection_id = Uuid::new_v4().to_string();
 
-        let tcp = TcpStream::connect(format!("{}:{}", config.host, config.port))
-            .map_err(|e| format!("Failed to connect: {}", e))?;
-
-        let mut session = Session::new()
-            .map_err(|e| format!("Failed to create session: {}", e))?;
-        session.set_tcp_stream(tcp);
-        session
-            .handshake()
-            .map_err(|e| format!("SSH handshake failed: {}", e))?;
-
-        if let Some(private_key_path) = &config.private_key_path {
-            session
-                .userauth_pubkey_file(
-                    &config.username,
-                    None,
-                    Path::new(private_key_path),
-                    config.passphrase.as_deref(),
-                )
-                .map_err(|e| format!("Key authentication failed: {}", e))?;
-        } else if let Some(password) = &config.password {
-            session
-                .userauth_password(&config.username, password)
-                .map_err(|e| format!("Password authentication failed: {}", e))?;
-        } else {
-            return Err("No authentication method provided".to_string());
+        // Each jump host is reached through the tunnel opened on the one
+        // before it; the last tunnel leads to the target itself.
+        let first_hop = config.jump_hosts.first().unwrap_or(&config);
+        let mut stream = connect_tcp(first_hop)?;
+
+        let mut tunnels = Vec::new();
+        for (i, jump) in config.jump_hosts.iter().enumerate() {
+            let next_hop = config.jump_hosts.get(i + 1).unwrap_or(&config);
+            let reborrowed = prompter
+                .as_mut()
+                .map(|p| &mut **p as &mut dyn KeyboardInteractivePrompt);
+            let (jump_session, _) = open_session(stream, jump, reborrowed)?;
+            let (tunnel, local) = JumpTunnel::open(jump_session, &next_hop.host, next_hop.port)?;
+            tunnels.push(tunnel);
+            stream = local;
         }
 
-        if !session.authenticated() {
-            return Err("Authentication failed".to_string());
-        }
+        let (session, auth_report) = open_session(stream, &config, prompter)?;
 
         let sftp = session
             .sftp()
-            .map_err(|e| format!("Failed to create SFTP session: {}", e))?;
+            .map_err(|e| SftpError::remote("open_sftp", &config.host, e, config.timeouts.operation))?;
 
         Ok(Self {
             session,
             sftp,
-            config,
+            config: if config.auto_reconnect {
+                config
+            } else {
+                config.without_secrets()
+            },
             connection_id,
+            auth_report,
+            _tunnels: tunnels,
         })
     }
 
@@ -77,11 +73,86 @@ impl SftpClient {
         &self.connection_id
     }
 
-    pub fn list_directory(&self, path: &str) -> Result<Vec<FileInfo>, String> {
+    pub fn auth_report(&self) -> &AuthReport {
+        &self.auth_report
+    }
+
+    pub fn host(&self) -> &str {
+        &self.config.host
+    }
+
+    pub fn auto_reconnect(&self) -> bool {
+        self.config.auto_reconnect
+    }
+
+    /// Sends a keepalive if one is due. An error means the session is gone.
+    pub fn keepalive(&self) -> Result<(), SftpError> {
+        self.session
+            .keepalive_send()
+            .map(|_| ())
+            .map_err(|e| self.remote_error("keepalive", &self.config.host, e))
+    }
+
+    /// Round-trips a cheap request to tell a dead session from an operation
+    /// that merely failed.
+    pub fn is_alive(&self) -> bool {
+        self.keepalive().is_ok() && self.sftp.realpath(Path::new(".")).is_ok()
+    }
+
+    /// Replaces the session (and any tunnels) with a fresh one built from the
+    /// stored config. The connection ID stays the same.
+    pub fn reconnect(
+        &mut self,
+        prompter: Option<&mut dyn KeyboardInteractivePrompt>,
+    ) -> Result<(), SftpError> {
+        let fresh = Self::connect(self.config.clone(), prompter)?;
+        let connection_id = std::mem::take(&mut self.connection_id);
+        *self = Self {
+            connection_id,
+            ..fresh
+        };
+        Ok(())
+    }
+
+    pub(crate) fn sftp(&self) -> &Sftp {
+        &self.sftp
+    }
+
+    /// Runs a command on the server and returns its output if it exited
+    /// successfully. Servers that only allow SFTP refuse the channel, which
+    /// also gives `None`.
+    pub(crate) fn exec(&self, command: &str) -> Option<String> {
+        let mut channel = self.session.channel_session().ok()?;
+        channel.exec(command).ok()?;
+
+        let mut output = String::new();
+        channel.read_to_string(&mut output).ok()?;
+        channel.wait_close().ok()?;
+        if channel.exit_status().ok()? != 0 {
+            return None;
+        }
+        Some(output)
+    }
+
+    pub(crate) fn remote_error(&self, operation: &str, path: &str, e: ssh2::Error) -> SftpError {
+        SftpError::remote(operation, path, e, self.config.timeouts.operation)
+    }
+
+    pub(crate) fn remote_io_error(&self, operation: &str, path: &str, e: std::io::Error) -> SftpError {
+        SftpError::remote_io(
+            operation,
+            path,
+            e,
+            ssh2::Error::last_session_error(&self.session),
+            self.config.timeouts.operation,
+        )
+    }
+
+    pub fn list_directory(&self, path: &str) -> Result<Vec<FileInfo>, SftpError> {
         let dir_path = Path::new(path);
         let entries = self.sftp
             .readdir(dir_path)
-            .map_err(|e| format!("Error reading directory: {}", e))?;
+            .map_err(|e| self.remote_error("list_directory", path, e))?;
 
         let mut files = Vec::new();
         for (file_path, stat) in entries {
@@ -122,304 +193,124 @@ impl SftpClient {
         Ok(files)
     }
 
-    fn upload_file_blocking(
-        &self,
-        local_path: &str,
-        remote_path: &str,
-        window: &Window,
-        transfer_id: &str,
-        cancel_flag: &Arc<AtomicBool>,
-    ) -> Result<(), String> {
-        let mut local_file = std::fs::File::open(local_path)
-            .map_err(|e| format!("Failed to open local file: {}", e))?;
-
-        let total_size = local_file
-            .metadata()
-            .map_err(|e| format!("Failed to stat local file: {}", e))?
-            .len();
-
-        let mut remote_file = self.sftp
-            .create(Path::new(remote_path))
-            .map_err(|e| format!("Failed to create remote file: {}", e))?;
-
-        let mut buffer = [0u8; 8192];
-        let mut transferred = 0u64;
-
-        loop {
-            if cancel_flag.load(Ordering::Relaxed) {
-                window.emit(
-                    "transfer_cancelled",
-                    serde_json::json!({
-                        "transfer_id": transfer_id,
-                        "type": "upload"
-                    }),
-                ).ok();
-                return Err("Transfer cancelled".to_string());
-            }
-
-            let n = local_file
-                .read(&mut buffer)
-                .map_err(|e| format!("Read error: {}", e))?;
-
-            if n == 0 {
-                break;
-            }
-
-            remote_file
-                .write_all(&buffer[..n])
-                .map_err(|e| format!("Write error: {}", e))?;
-
-            transferred += n as u64;
-
-            window.emit(
-                "upload_progress",
-                serde_json::json!({
-                    "connection_id": self.connection_id,
-                    "path": remote_path,
-                    "transferred": transferred,
-                    "total": total_size,
-                    "type": "upload",
-                    "transfer_id": transfer_id
-                }),
-            ).ok();
-        }
-
-        window.emit(
-            "process_finished",
-            serde_json::json!({
-                "connection_id": self.connection_id,
-                "path": remote_path,
-                "type": "upload",
-                "transfer_id": transfer_id
-            }),
-        ).ok();
-
-        Ok(())
-    }
-
-    fn download_file_blocking(
-        &self,
-        remote_path: &str,
-        local_path: &str,
-        window: &Window,
-        transfer_id: &str,
-        cancel_flag: &Arc<AtomicBool>,
-    ) -> Result<(), String> {
-        let mut remote_file = self.sftp
-            .open(Path::new(remote_path))
-            .map_err(|e| format!("Failed to open remote file: {}", e))?;
-
-        let total_size = remote_file
-            .stat()
-            .map_err(|e| format!("Failed to stat remote file: {}", e))?
-            .size
-            .unwrap_or(0);
-
-        let mut local_file = std::fs::File::create(local_path)
-            .map_err(|e| format!("Failed to create local file: {}", e))?;
-
-        let mut buffer = [0u8; 8192];
-        let mut transferred = 0u64;
-
-        loop {
-            if cancel_flag.load(Ordering::Relaxed) {
-                drop(local_file);
-                std::fs::remove_file(local_path).ok();
-                
-                window.emit(
-                    "transfer_cancelled",
-                    serde_json::json!({
-                        "transfer_id": transfer_id,
-                        "type": "download"
-                    }),
-                ).ok();
-                return Err("Transfer cancelled".to_string());
-            }
-
-            let n = remote_file
-                .read(&mut buffer)
-                .map_err(|e| format!("Read error: {}", e))?;
-
-            if n == 0 {
-                break;
-            }
-
-            local_file
-                .write_all(&buffer[..n])
-                .map_err(|e| format!("Write error: {}", e))?;
-
-            transferred += n as u64;
-
-            window.emit(
-                "download_progress",
-                serde_json::json!({
-                    "connection_id": self.connection_id,
-                    "path": remote_path,
-                    "transferred": transferred,
-                    "total": total_size,
-                    "type": "download",
-                    "transfer_id": transfer_id
-                }),
-            ).ok();
-        }
-
-        local_file
-            .sync_all()
-            .map_err(|e| format!("Failed to sync file: {}", e))?;
-
-        window.emit(
-            "process_finished",
-            serde_json::json!({
-                "connection_id": self.connection_id,
-                "path": remote_path,
-                "type": "download",
-                "transfer_id": transfer_id
-            }),
-        ).ok();
-
-        Ok(())
-    }
-
-    pub fn delete(&self, path: &str, is_dir: bool) -> Result<(), String> {
-        let path = Path::new(path);
+    pub fn delete(&self, path: &str, is_dir: bool) -> Result<(), SftpError> {
+        let remote_path = Path::new(path);
         
         if is_dir {
-            self.sftp.rmdir(path)
-                .map_err(|e| format!("Failed to delete directory: {}", e))?;
+            self.sftp.rmdir(remote_path)
+                .map_err(|e| self.remote_error("delete", path, e))?;
         } else {
-            self.sftp.unlink(path)
-                .map_err(|e| format!("Failed to delete file: {}", e))?;
+            self.sftp.unlink(remote_path)
+                .map_err(|e| self.remote_error("delete", path, e))?;
         }
         
         Ok(())
     }
 
-    pub fn create_directory(&self, path: &str) -> Result<(), String> {
+    pub fn create_directory(&self, path: &str) -> Result<(), SftpError> {
         self.sftp.mkdir(Path::new(path), 0o755)
-            .map_err(|e| format!("Failed to create directory: {}", e))
+            .map_err(|e| self.remote_error("create_directory", path, e))
     }
 
-    pub fn rename(&self, old_path: &str, new_path: &str) -> Result<(), String> {
+    pub fn rename(&self, old_path: &str, new_path: &str) -> Result<(), SftpError> {
         self.sftp.rename(Path::new(old_path), Path::new(new_path), None)
-            .map_err(|e| format!("Failed to rename: {}", e))
+            .map_err(|e| self.remote_error("rename", old_path, e))
     }
 
 }
 
-
-pub fn cancel_transfer(transfer_id: &str) -> Result<(), String> {
-    let map = TRANSFER_CANCEL_MAP.lock().unwrap();
-    if let Some(cancel_flag) = map.get(transfer_id) {
-        cancel_flag.store(true, Ordering::Relaxed);
-        Ok(())
-    } else {
-        Err("Transfer not found".to_string())
+/// Connects to the first hop, trying each address it resolves to within
+/// the connect timeout.
+fn connect_tcp(config: &ConnectionConfig) -> Result<TcpStream, SftpError> {
+    let seconds = config.timeouts.connect;
+    if seconds == 0 {
+        return TcpStream::connect((config.host.as_str(), config.port))
+            .map_err(|e| format!("Failed to connect to {}: {}", config.host, e).into());
     }
-}
 
-pub fn start_upload(
-    local_path: String,
-    remote_path: String,
-    window: Window,
-    client_arc: Arc<Mutex<SftpClient>>,
-) -> Result<String, String> {
-    let transfer_id = Uuid::new_v4().to_string();
-    let cancel_flag = Arc::new(AtomicBool::new(false));
-
-    TRANSFER_CANCEL_MAP
-        .lock()
-        .unwrap()
-        .insert(transfer_id.clone(), cancel_flag.clone());
-
-    let transfer_id_clone = transfer_id.clone();
-
-    tokio::task::spawn_blocking(move || {
-        let result = {
-            let client = client_arc.lock()
-                .map_err(|e| format!("Failed to lock client: {}", e));
-            
-            match client {
-                Ok(client) => {
-                    client.upload_file_blocking(
-                        &local_path,
-                        &remote_path,
-                        &window,
-                        &transfer_id_clone,
-                        &cancel_flag,
-                    )
-                }
-                Err(e) => Err(e),
-            }
-        };
+    let addrs = (config.host.as_str(), config.port)
+        .to_socket_addrs()
+        .map_err(|e| format!("Failed to resolve {}: {}", config.host, e))?;
 
-        TRANSFER_CANCEL_MAP.lock().unwrap().remove(&transfer_id_clone);
-
-        if let Err(e) = result {
-            window.emit(
-                "transfer_error",
-                serde_json::json!({
-                    "transfer_id": transfer_id_clone,
-                    "error": e,
-                    "type": "upload"
-                }),
-            ).ok();
+    let mut last_error = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, Duration::from_secs(seconds.into())) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
         }
-    });
+    }
 
-    Ok(transfer_id)
+    Err(match last_error {
+        Some(e) if e.kind() == ErrorKind::TimedOut => {
+            SftpError::timeout("connect", &config.host, seconds)
+        }
+        Some(e) => format!("Failed to connect to {}: {}", config.host, e).into(),
+        None => format!("Failed to resolve {}: no addresses found", config.host).into(),
+    })
 }
 
-pub fn start_download(
-    remote_path: String,
-    local_path: String,
-    window: Window,
-    client_arc: Arc<Mutex<SftpClient>>,
-) -> Result<String, String> {
-    let transfer_id = Uuid::new_v4().to_string();
-    let cancel_flag = Arc::new(AtomicBool::new(false));
-
-    TRANSFER_CANCEL_MAP
-        .lock()
-        .unwrap()
-        .insert(transfer_id.clone(), cancel_flag.clone());
-
-    let transfer_id_clone = transfer_id.clone();
-    let local_path_clone = local_path.clone();
-
-    tokio::task::spawn_blocking(move || {
-        let result = {
-            let client = client_arc.lock()
-                .map_err(|e| format!("Failed to lock client: {}", e));
-            
-            match client {
-                Ok(client) => {
-                    client.download_file_blocking(
-                        &remote_path,
-                        &local_path,
-                        &window,
-                        &transfer_id_clone,
-                        &cancel_flag,
-                    )
-                }
-                Err(e) => Err(e),
-            }
-        };
+/// Handshakes over `stream`, checks the host key and authenticates.
+fn open_session(
+    stream: TcpStream,
+    config: &ConnectionConfig,
+    prompter: Option<&mut dyn KeyboardInteractivePrompt>,
+) -> Result<(Session, AuthReport), SftpError> {
+    let known_hosts_path =
+        known_hosts::resolve_known_hosts_path(config.known_hosts_path.as_deref())?;
+
+    let timeouts = config.timeouts;
+    let mut session = Session::new()
+        .map_err(|e| format!("Failed to create session: {}", e))?;
+    session.set_tcp_stream(stream);
+    known_hosts::prefer_known_key_types(&session, &known_hosts_path, &config.host, config.port);
+    session.set_timeout(timeouts.handshake.saturating_mul(1000));
+    session.handshake().map_err(|e| {
+        if is_timeout(&e) {
+            SftpError::timeout("handshake", &config.host, timeouts.handshake)
+        } else {
+            format!("SSH handshake with {} failed: {}", config.host, e).into()
+        }
+    })?;
 
-        TRANSFER_CANCEL_MAP.lock().unwrap().remove(&transfer_id_clone);
+    verify_host_key(&session, config, &known_hosts_path)?;
 
-        if let Err(e) = result {
-            std::fs::remove_file(&local_path_clone).ok();
-            
-            window.emit(
-                "transfer_error",
-                serde_json::json!({
-                    "transfer_id": transfer_id_clone,
-                    "error": e,
-                    "type": "download"
-                }),
-            ).ok();
-        }
-    });
+    session.set_timeout(timeouts.auth.saturating_mul(1000));
+    let auth_report = auth::authenticate(&session, config, prompter);
+    if auth_report.timed_out {
+        return Err(SftpError::timeout("authenticate", &config.host, timeouts.auth));
+    }
+    if !session.authenticated() {
+        return Err(SftpError::AuthFailed(auth_report));
+    }
+    session.set_timeout(timeouts.operation.saturating_mul(1000));
+
+    let keepalive_interval = config.keepalive_interval();
+    if keepalive_interval > 0 {
+        session.set_keepalive(true, keepalive_interval);
+    }
 
-    Ok(transfer_id)
-}
\ No newline at end of file
+    Ok((session, auth_report))
+}
+
+fn verify_host_key(
+    session: &Session,
+    config: &ConnectionConfig,
+    known_hosts_path: &Path,
+) -> Result<(), SftpError> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| "Server did not provide a host key".to_string())?;
+
+    match known_hosts::check_host_key(known_hosts_path, &config.host, config.port, key)? {
+        HostKeyStatus::Trusted => Ok(()),
+        HostKeyStatus::Unknown => Err(SftpError::UnknownHost(
+            known_hosts::remember_pending_key(&config.host, config.port, key),
+        )),
+        HostKeyStatus::Changed => Err(SftpError::HostKeyMismatch(
+            known_hosts::remember_pending_key(&config.host, config.port, key),
+        )),
+        HostKeyStatus::Revoked => Err(SftpError::HostKeyRevoked(
+            known_hosts::host_key_info(&config.host, config.port, key),
+        )),
+    }
+}
@@ -0,0 +1,317 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Window};
+use uuid::Uuid;
+
+use crate::sftp::SftpError;
+
+/// How often a transfer waiting on an answer checks whether it was cancelled.
+const CANCEL_POLL: Duration = Duration::from_millis(250);
+
+type ConflictSender = Sender<Option<ConflictAnswer>>;
+
+static PENDING_CONFLICTS: Lazy<Mutex<HashMap<String, ConflictSender>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// What to do when a file's destination already exists.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    #[default]
+    Overwrite,
+    Skip,
+    /// Copy to the first free `name (n).ext` next to the existing file.
+    Rename,
+    /// Overwrite only if the incoming file was modified more recently.
+    Newer,
+    /// Overwrite only if the sizes differ.
+    SizeDiffers,
+    /// Send a `transfer_conflict` event and wait for the user's answer.
+    Ask,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictResolution {
+    Overwrite,
+    Skip,
+    Rename,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub struct ConflictAnswer {
+    pub resolution: ConflictResolution,
+    /// Use the same answer for every later conflict in this transfer.
+    #[serde(default)]
+    pub apply_to_all: bool,
+}
+
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct FileSummary {
+    pub size: u64,
+    /// Unix seconds.
+    pub modified: Option<u64>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct Conflict<'a> {
+    pub transfer_id: &'a str,
+    #[serde(rename = "type")]
+    pub kind: &'a str,
+    pub source: &'a str,
+    pub destination: &'a str,
+    pub incoming: FileSummary,
+    pub existing: FileSummary,
+    /// Part of a directory transfer, where "apply to all" makes sense.
+    pub batch: bool,
+}
+
+#[derive(Serialize, Clone)]
+struct ConflictEvent<'a> {
+    prompt_id: &'a str,
+    #[serde(flatten)]
+    conflict: &'a Conflict<'a>,
+}
+
+/// The answers given so far in one transfer. Kept across retries, and
+/// carried in the record for `retry_transfer`, so an attempt that resumes
+/// writes to the same place the first one did.
+#[derive(Default)]
+pub struct ConflictDecisions {
+    /// Original destination to the one actually written, `None` if skipped.
+    decided: HashMap<String, Option<String>>,
+    apply_to_all: Option<ConflictResolution>,
+}
+
+impl From<HashMap<String, Option<String>>> for ConflictDecisions {
+    fn from(decided: HashMap<String, Option<String>>) -> Self {
+        Self {
+            decided,
+            apply_to_all: None,
+        }
+    }
+}
+
+impl ConflictDecisions {
+    pub fn decided(&self) -> &HashMap<String, Option<String>> {
+        &self.decided
+    }
+
+    pub fn get(&self, destination: &str) -> Option<Option<String>> {
+        self.decided.get(destination).cloned()
+    }
+
+    pub fn remember(&mut self, destination: &str, target: Option<String>) {
+        self.decided.insert(destination.to_string(), target);
+    }
+
+    pub fn apply_to_all(&self) -> Option<ConflictResolution> {
+        self.apply_to_all
+    }
+
+    pub fn set_apply_to_all(&mut self, resolution: ConflictResolution) {
+        self.apply_to_all = Some(resolution);
+    }
+}
+
+/// Settles a conflict by policy alone. `None` means the user has to be asked.
+pub fn decide(
+    policy: ConflictPolicy,
+    incoming: &FileSummary,
+    existing: &FileSummary,
+) -> Option<ConflictResolution> {
+    let overwrite_if = |overwrite: bool| {
+        Some(if overwrite {
+            ConflictResolution::Overwrite
+        } else {
+            ConflictResolution::Skip
+        })
+    };
+
+    match policy {
+        ConflictPolicy::Overwrite => Some(ConflictResolution::Overwrite),
+        ConflictPolicy::Skip => Some(ConflictResolution::Skip),
+        ConflictPolicy::Rename => Some(ConflictResolution::Rename),
+        // Without both times there's no telling which is newer, so the
+        // existing file is left alone.
+        ConflictPolicy::Newer => overwrite_if(matches!(
+            (incoming.modified, existing.modified),
+            (Some(incoming), Some(existing)) if incoming > existing
+        )),
+        ConflictPolicy::SizeDiffers => overwrite_if(incoming.size != existing.size),
+        ConflictPolicy::Ask => None,
+    }
+}
+
+/// `dir/name (n).ext`, keeping the extension after the last dot. A leading
+/// dot, as in `.bashrc`, doesn't count as one.
+pub fn numbered_path(path: &str, n: u32) -> String {
+    let name_start = path.rfind(['/', '\\']).map_or(0, |index| index + 1);
+    let (dir, name) = path.split_at(name_start);
+    let (stem, extension) = match name.rfind('.') {
+        Some(index) if index > 0 => name.split_at(index),
+        _ => (name, ""),
+    };
+    format!("{}{} ({}){}", dir, stem, n, extension)
+}
+
+/// Raises a `transfer_conflict` event and blocks until
+/// `respond_to_conflict` delivers the answer. Returns `None` if the prompt
+/// was dismissed or `cancelled` turns true while waiting.
+pub fn ask(
+    window: &Window,
+    conflict: &Conflict,
+    cancelled: &dyn Fn() -> bool,
+) -> Option<ConflictAnswer> {
+    let prompt_id = Uuid::new_v4().to_string();
+    let (tx, rx) = mpsc::channel();
+    PENDING_CONFLICTS.lock().unwrap().insert(prompt_id.clone(), tx);
+
+    window.emit(
+        "transfer_conflict",
+        ConflictEvent {
+            prompt_id: &prompt_id,
+            conflict,
+        },
+    ).ok();
+
+    let answer = loop {
+        match rx.recv_timeout(CANCEL_POLL) {
+            Ok(answer) => break answer,
+            Err(RecvTimeoutError::Timeout) if !cancelled() => continue,
+            Err(_) => break None,
+        }
+    };
+    PENDING_CONFLICTS.lock().unwrap().remove(&prompt_id);
+    answer
+}
+
+/// Delivers the user's answer for a pending conflict; `None` cancels the
+/// transfer.
+pub fn respond_to_conflict(
+    prompt_id: &str,
+    answer: Option<ConflictAnswer>,
+) -> Result<(), SftpError> {
+    let not_found = || SftpError::ConflictPromptNotFound {
+        prompt_id: prompt_id.to_string(),
+    };
+    let sender = PENDING_CONFLICTS
+        .lock()
+        .unwrap()
+        .remove(prompt_id)
+        .ok_or_else(not_found)?;
+
+    sender.send(answer).map_err(|_| not_found())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(size: u64, modified: Option<u64>) -> FileSummary {
+        FileSummary { size, modified }
+    }
+
+    #[test]
+    fn fixed_policies_ignore_the_files() {
+        let (incoming, existing) = (summary(1, Some(1)), summary(2, Some(2)));
+        let decide = |policy| decide(policy, &incoming, &existing);
+        assert_eq!(decide(ConflictPolicy::Overwrite), Some(ConflictResolution::Overwrite));
+        assert_eq!(decide(ConflictPolicy::Skip), Some(ConflictResolution::Skip));
+        assert_eq!(decide(ConflictPolicy::Rename), Some(ConflictResolution::Rename));
+        assert_eq!(decide(ConflictPolicy::Ask), None);
+    }
+
+    #[test]
+    fn newer_needs_both_times() {
+        let newer = |incoming, existing| {
+            decide(ConflictPolicy::Newer, &summary(1, incoming), &summary(1, existing))
+        };
+        assert_eq!(newer(Some(20), Some(10)), Some(ConflictResolution::Overwrite));
+        assert_eq!(newer(Some(10), Some(10)), Some(ConflictResolution::Skip));
+        assert_eq!(newer(Some(5), Some(10)), Some(ConflictResolution::Skip));
+        assert_eq!(newer(None, Some(10)), Some(ConflictResolution::Skip));
+        assert_eq!(newer(Some(20), None), Some(ConflictResolution::Skip));
+    }
+
+    #[test]
+    fn size_differs_compares_sizes_only() {
+        let sizes = |incoming, existing| {
+            let policy = ConflictPolicy::SizeDiffers;
+            decide(policy, &summary(incoming, Some(1)), &summary(existing, None))
+        };
+        assert_eq!(sizes(10, 20), Some(ConflictResolution::Overwrite));
+        assert_eq!(sizes(10, 10), Some(ConflictResolution::Skip));
+    }
+
+    #[test]
+    fn numbered_paths_keep_the_extension() {
+        assert_eq!(numbered_path("/data/report.csv", 1), "/data/report (1).csv");
+        assert_eq!(numbered_path("/data/archive.tar.gz", 2), "/data/archive.tar (2).gz");
+        assert_eq!(numbered_path("/home/me/.bashrc", 1), "/home/me/.bashrc (1)");
+        assert_eq!(numbered_path("README", 3), "README (3)");
+        assert_eq!(numbered_path("/dir.d/file", 1), "/dir.d/file (1)");
+        assert_eq!(numbered_path(r"C:\Users\me\notes.txt", 1), r"C:\Users\me\notes (1).txt");
+    }
+
+    #[test]
+    fn answers_reach_the_waiting_transfer_once() {
+        let (tx, rx) = mpsc::channel();
+        PENDING_CONFLICTS.lock().unwrap().insert("prompt".to_string(), tx);
+        let answer = ConflictAnswer {
+            resolution: ConflictResolution::Rename,
+            apply_to_all: true,
+        };
+
+        respond_to_conflict("prompt", Some(answer)).unwrap();
+        let received = rx.recv().unwrap().unwrap();
+        assert_eq!(received.resolution, ConflictResolution::Rename);
+        assert!(matches!(
+            respond_to_conflict("prompt", None),
+            Err(SftpError::ConflictPromptNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn answers_fail_once_the_transfer_stopped_waiting() {
+        let (tx, rx) = mpsc::channel();
+        PENDING_CONFLICTS.lock().unwrap().insert("abandoned".to_string(), tx);
+        drop(rx);
+        assert!(matches!(
+            respond_to_conflict("abandoned", None),
+            Err(SftpError::ConflictPromptNotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn decisions_are_remembered_per_destination() {
+        let mut decisions = ConflictDecisions::default();
+        assert_eq!(decisions.get("/a"), None);
+        decisions.remember("/a", Some("/a (1)".to_string()));
+        decisions.remember("/b", None);
+        assert_eq!(decisions.get("/a"), Some(Some("/a (1)".to_string())));
+        assert_eq!(decisions.get("/b"), Some(None));
+
+        assert_eq!(decisions.apply_to_all(), None);
+        decisions.set_apply_to_all(ConflictResolution::Skip);
+        assert_eq!(decisions.apply_to_all(), Some(ConflictResolution::Skip));
+    }
+
+    #[test]
+    fn carried_over_decisions_still_apply() {
+        let mut decided = HashMap::new();
+        decided.insert("/a".to_string(), Some("/a (1)".to_string()));
+        let mut decisions = ConflictDecisions::from(decided.clone());
+        assert_eq!(decisions.get("/a"), Some(Some("/a (1)".to_string())));
+        assert_eq!(decisions.apply_to_all(), None);
+
+        decisions.remember("/b", None);
+        decided.insert("/b".to_string(), None);
+        assert_eq!(decisions.decided(), &decided);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+use std::io;
+
+use serde::Serialize;
+use ssh2::ErrorCode;
+
+use crate::sftp::known_hosts::HostKeyInfo;
+use crate::sftp::types::AuthReport;
+use crate::sftp::utils::is_timeout;
+
+// libssh2 session errors (LIBSSH2_ERROR_*) that mean the transport is gone.
+const LIBSSH2_ERROR_SOCKET_SEND: i32 = -7;
+const LIBSSH2_ERROR_SOCKET_DISCONNECT: i32 = -13;
+const LIBSSH2_ERROR_CHANNEL_CLOSED: i32 = -26;
+const LIBSSH2_ERROR_CHANNEL_EOF_SENT: i32 = -27;
+const LIBSSH2_ERROR_SOCKET_TIMEOUT: i32 = -30;
+const LIBSSH2_ERROR_SOCKET_RECV: i32 = -43;
+
+// SFTP status codes (SSH_FX_*).
+const FX_NO_SUCH_FILE: i32 = 2;
+const FX_PERMISSION_DENIED: i32 = 3;
+const FX_NO_CONNECTION: i32 = 6;
+const FX_CONNECTION_LOST: i32 = 7;
+const FX_NO_SUCH_PATH: i32 = 10;
+const FX_FILE_ALREADY_EXISTS: i32 = 11;
+const FX_WRITE_PROTECT: i32 = 12;
+const FX_NO_SPACE_ON_FILESYSTEM: i32 = 14;
+const FX_QUOTA_EXCEEDED: i32 = 15;
+const FX_DIR_NOT_EMPTY: i32 = 18;
+
+/// One file that failed within a directory transfer.
+#[derive(Serialize, Debug)]
+pub struct FileError {
+    pub local_path: String,
+    pub remote_path: String,
+    pub error: SftpError,
+}
+
+/// Errors returned by the SFTP client and every Tauri command. Serialized
+/// with a `kind` tag so the frontend can branch on it; file-level variants
+/// name the operation and the path it was working on.
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SftpError {
+    NoSuchFile { operation: String, path: String },
+    PermissionDenied { operation: String, path: String },
+    AlreadyExists { operation: String, path: String },
+    DirectoryNotEmpty { operation: String, path: String },
+    NoSpace { operation: String, path: String },
+    Failure { operation: String, path: String, message: String },
+    LocalIo { operation: String, path: String, message: String },
+    ConnectionLost { operation: String, message: String },
+    ConnectionNotFound { connection_id: String },
+    TransferNotFound { transfer_id: String },
+    HistoryEntryNotFound { entry_id: String },
+    /// The prompt was already answered, or its transfer stopped waiting.
+    ConflictPromptNotFound { prompt_id: String },
+    /// `target` is the path for file operations and the host while connecting.
+    Timeout { operation: String, target: String, seconds: u32 },
+    Cancelled { operation: String, target: String },
+    ChecksumMismatch { path: String, local: String, remote: String },
+    /// A directory transfer finished, but some of its files didn't make it.
+    PartialFailure { operation: String, path: String, failed: Vec<FileError> },
+    UnknownHost(HostKeyInfo),
+    HostKeyMismatch(HostKeyInfo),
+    HostKeyRevoked(HostKeyInfo),
+    AuthFailed(AuthReport),
+    Other { message: String },
+}
+
+impl SftpError {
+    /// Classifies an error from libssh2, either an SFTP status code or a
+    /// session-level failure. `timeout` is what gets reported on a timeout.
+    pub fn remote(operation: &str, path: &str, error: ssh2::Error, timeout: u32) -> Self {
+        let operation = operation.to_string();
+        let path = path.to_string();
+
+        match error.code() {
+            ErrorCode::SFTP(FX_NO_SUCH_FILE | FX_NO_SUCH_PATH) => {
+                SftpError::NoSuchFile { operation, path }
+            }
+            ErrorCode::SFTP(FX_PERMISSION_DENIED | FX_WRITE_PROTECT) => {
+                SftpError::PermissionDenied { operation, path }
+            }
+            ErrorCode::SFTP(FX_FILE_ALREADY_EXISTS) => SftpError::AlreadyExists { operation, path },
+            ErrorCode::SFTP(FX_DIR_NOT_EMPTY) => SftpError::DirectoryNotEmpty { operation, path },
+            ErrorCode::SFTP(FX_NO_SPACE_ON_FILESYSTEM | FX_QUOTA_EXCEEDED) => {
+                SftpError::NoSpace { operation, path }
+            }
+            ErrorCode::SFTP(FX_NO_CONNECTION | FX_CONNECTION_LOST)
+            | ErrorCode::Session(
+                LIBSSH2_ERROR_SOCKET_SEND
+                | LIBSSH2_ERROR_SOCKET_DISCONNECT
+                | LIBSSH2_ERROR_CHANNEL_CLOSED
+                | LIBSSH2_ERROR_CHANNEL_EOF_SENT
+                | LIBSSH2_ERROR_SOCKET_TIMEOUT
+                | LIBSSH2_ERROR_SOCKET_RECV,
+            ) => SftpError::ConnectionLost {
+                operation,
+                message: error.message().to_string(),
+            },
+            _ if is_timeout(&error) => SftpError::Timeout {
+                operation,
+                target: path,
+                seconds: timeout,
+            },
+            _ => SftpError::Failure {
+                operation,
+                path,
+                message: error.message().to_string(),
+            },
+        }
+    }
+
+    /// Classifies an I/O error from reading or writing a remote file. ssh2
+    /// only keeps a coarse kind and the message, so the session's last error
+    /// is used to tell a dropped connection from a failed request.
+    pub fn remote_io(
+        operation: &str,
+        path: &str,
+        error: io::Error,
+        last_error: Option<ssh2::Error>,
+        timeout: u32,
+    ) -> Self {
+        match error.kind() {
+            io::ErrorKind::TimedOut => SftpError::timeout(operation, path, timeout),
+            io::ErrorKind::NotFound => SftpError::NoSuchFile {
+                operation: operation.to_string(),
+                path: path.to_string(),
+            },
+            _ => match last_error.map(|e| SftpError::remote(operation, path, e, timeout)) {
+                Some(lost @ SftpError::ConnectionLost { .. }) => lost,
+                _ => SftpError::Failure {
+                    operation: operation.to_string(),
+                    path: path.to_string(),
+                    message: error.to_string(),
+                },
+            },
+        }
+    }
+
+    pub fn local(operation: &str, path: &str, error: io::Error) -> Self {
+        SftpError::LocalIo {
+            operation: operation.to_string(),
+            path: path.to_string(),
+            message: error.to_string(),
+        }
+    }
+
+    pub fn cancelled(operation: &str, target: &str) -> Self {
+        SftpError::Cancelled {
+            operation: operation.to_string(),
+            target: target.to_string(),
+        }
+    }
+
+    pub fn timeout(operation: &str, target: &str, seconds: u32) -> Self {
+        SftpError::Timeout {
+            operation: operation.to_string(),
+            target: target.to_string(),
+            seconds,
+        }
+    }
+
+    /// Whether the session itself may be unusable, as opposed to a single
+    /// request having been refused.
+    pub fn is_connection_error(&self) -> bool {
+        matches!(self, SftpError::ConnectionLost { .. } | SftpError::Timeout { .. })
+    }
+}
+
+impl fmt::Display for SftpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SftpError::NoSuchFile { operation, path } => {
+                write!(f, "{} failed: {} does not exist", operation, path)
+            }
+            SftpError::PermissionDenied { operation, path } => {
+                write!(f, "{} failed: permission denied for {}", operation, path)
+            }
+            SftpError::AlreadyExists { operation, path } => {
+                write!(f, "{} failed: {} already exists", operation, path)
+            }
+            SftpError::DirectoryNotEmpty { operation, path } => {
+                write!(f, "{} failed: directory {} is not empty", operation, path)
+            }
+            SftpError::NoSpace { operation, path } => {
+                write!(f, "{} failed: no space left for {}", operation, path)
+            }
+            SftpError::Failure {
+                operation,
+                path,
+                message,
+            } => write!(f, "{} failed for {}: {}", operation, path, message),
+            SftpError::LocalIo {
+                operation,
+                path,
+                message,
+            } => write!(f, "{} failed for local file {}: {}", operation, path, message),
+            SftpError::ConnectionLost { operation, message } => {
+                write!(f, "Connection lost during {}: {}", operation, message)
+            }
+            SftpError::ConnectionNotFound { connection_id } => {
+                write!(f, "Connection {} not found", connection_id)
+            }
+            SftpError::TransferNotFound { transfer_id } => {
+                write!(f, "Transfer {} not found", transfer_id)
+            }
+            SftpError::HistoryEntryNotFound { entry_id } => {
+                write!(f, "History entry {} not found", entry_id)
+            }
+            SftpError::ConflictPromptNotFound { prompt_id } => {
+                write!(f, "Conflict prompt {} is no longer waiting for an answer", prompt_id)
+            }
+            SftpError::Timeout {
+                operation,
+                target,
+                seconds,
+            } => write!(f, "{} timed out for {} after {}s", operation, target, seconds),
+            SftpError::Cancelled { operation, target } => {
+                write!(f, "{} cancelled for {}", operation, target)
+            }
+            SftpError::ChecksumMismatch { path, local, remote } => write!(
+                f,
+                "Checksum mismatch for {}: local {}, remote {}",
+                path, local, remote
+            ),
+            SftpError::PartialFailure {
+                operation,
+                path,
+                failed,
+            } => {
+                let paths: Vec<&str> =
+                    failed.iter().map(|file| file.remote_path.as_str()).collect();
+                write!(
+                    f,
+                    "{} of {} failed for {} item(s): {}",
+                    operation,
+                    path,
+                    failed.len(),
+                    paths.join(", ")
+                )
+            }
+            SftpError::UnknownHost(info) => write!(
+                f,
+                "Unknown host {}:{} ({} key fingerprint {})",
+                info.host, info.port, info.key_type, info.fingerprint
+            ),
+            SftpError::HostKeyMismatch(info) => write!(
+                f,
+                "Host key for {}:{} has changed ({} key fingerprint {})",
+                info.host, info.port, info.key_type, info.fingerprint
+            ),
+            SftpError::HostKeyRevoked(info) => write!(
+                f,
+                "Host key for {}:{} is revoked ({} key fingerprint {})",
+                info.host, info.port, info.key_type, info.fingerprint
+            ),
+            SftpError::AuthFailed(report) => {
+                write!(f, "Authentication to {} failed: {}", report.host, report.summary())
+            }
+            SftpError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for SftpError {}
+
+impl From<String> for SftpError {
+    fn from(message: String) -> Self {
+        SftpError::Other { message }
+    }
+}
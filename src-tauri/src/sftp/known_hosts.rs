@@ -0,0 +1,484 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use sha1::Sha1;
+use ssh2::{MethodType, Session};
+
+use crate::sftp::utils::{format_fingerprint, match_pattern_list};
+
+/// Host keys seen during a connection attempt that the user has not trusted
+/// yet, keyed by `host:port`. `trust_host_key` only persists a key that was
+/// actually presented by the server.
+static PENDING_HOST_KEYS: Lazy<Mutex<HashMap<String, PendingHostKey>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+struct PendingHostKey {
+    key_type: String,
+    key: Vec<u8>,
+    fingerprint: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct HostKeyInfo {
+    pub host: String,
+    pub port: u16,
+    pub key_type: String,
+    pub fingerprint: String,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum HostKeyStatus {
+    Trusted,
+    Unknown,
+    Changed,
+    Revoked,
+}
+
+struct KnownHostEntry {
+    marker: Option<String>,
+    patterns: String,
+    key_type: String,
+    key: Vec<u8>,
+}
+
+pub fn default_known_hosts_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".ssh").join("known_hosts"))
+}
+
+pub fn resolve_known_hosts_path(path: Option<&str>) -> Result<PathBuf, String> {
+    match path {
+        Some(path) => Ok(PathBuf::from(path)),
+        None => default_known_hosts_path()
+            .ok_or_else(|| "Could not determine home directory".to_string()),
+    }
+}
+
+/// The name OpenSSH records for a host: bare for port 22, `[host]:port` otherwise.
+fn host_entry_name(host: &str, port: u16) -> String {
+    let host = host.to_lowercase();
+    if port == 22 {
+        host
+    } else {
+        format!("[{}]:{}", host, port)
+    }
+}
+
+/// Reads the algorithm name embedded at the start of an SSH public key blob.
+pub fn key_type_from_blob(key: &[u8]) -> Option<String> {
+    let len = u32::from_be_bytes(key.get(..4)?.try_into().ok()?) as usize;
+    let name = key.get(4..4 + len)?;
+    String::from_utf8(name.to_vec()).ok()
+}
+
+fn parse_line(line: &str) -> Option<KnownHostEntry> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let mut fields = line.split_whitespace();
+    let mut first = fields.next()?;
+    let marker = if first.starts_with('@') {
+        let marker = first.to_string();
+        first = fields.next()?;
+        Some(marker)
+    } else {
+        None
+    };
+
+    let key_type = fields.next()?.to_string();
+    let key = STANDARD.decode(fields.next()?).ok()?;
+
+    Some(KnownHostEntry {
+        marker,
+        patterns: first.to_string(),
+        key_type,
+        key,
+    })
+}
+
+fn hashed_host_matches(pattern: &str, name: &str) -> bool {
+    // |1|base64(salt)|base64(HMAC-SHA1(salt, name))
+    let mut parts = pattern.trim_start_matches("|1|").splitn(2, '|');
+    let (Some(salt), Some(hash)) = (parts.next(), parts.next()) else {
+        return false;
+    };
+    let (Ok(salt), Ok(hash)) = (STANDARD.decode(salt), STANDARD.decode(hash)) else {
+        return false;
+    };
+
+    let Ok(mut mac) = Hmac::<Sha1>::new_from_slice(&salt) else {
+        return false;
+    };
+    mac.update(name.as_bytes());
+    mac.verify_slice(&hash).is_ok()
+}
+
+fn patterns_match(patterns: &str, name: &str) -> bool {
+    if patterns.starts_with("|1|") {
+        return hashed_host_matches(patterns, name);
+    }
+    match_pattern_list(patterns.split(','), name)
+}
+
+fn read_entries(path: &Path) -> Result<Vec<KnownHostEntry>, String> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents.lines().filter_map(parse_line).collect()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(format!("Failed to read known_hosts: {}", e)),
+    }
+}
+
+fn known_key_types(path: &Path, host: &str, port: u16) -> Vec<String> {
+    let name = host_entry_name(host, port);
+    let mut types = Vec::new();
+    for entry in read_entries(path).unwrap_or_default() {
+        if entry.marker.is_none()
+            && patterns_match(&entry.patterns, &name)
+            && !types.contains(&entry.key_type)
+        {
+            types.push(entry.key_type);
+        }
+    }
+    types
+}
+
+/// Moves the host key algorithms we already have keys for to the front of the
+/// negotiation list, so a known host isn't reported as unknown just because
+/// the server prefers a different key type.
+pub fn prefer_known_key_types(session: &Session, path: &Path, host: &str, port: u16) {
+    let known_types = known_key_types(path, host, port);
+    if known_types.is_empty() {
+        return;
+    }
+
+    let Ok(supported) = session.supported_algs(MethodType::HostKey) else {
+        return;
+    };
+
+    let preferred = preferred_key_types(&supported, &known_types);
+    session.method_pref(MethodType::HostKey, &preferred.join(",")).ok();
+}
+
+/// `supported` with the algorithms matching a known key type first. An
+/// `ssh-rsa` key can be verified with any of the `rsa-sha2-*` algorithms.
+fn preferred_key_types<'a>(supported: &[&'a str], known_types: &[String]) -> Vec<&'a str> {
+    let is_known = |alg: &str| {
+        known_types.iter().any(|key_type| {
+            key_type == alg || (key_type == "ssh-rsa" && alg.starts_with("rsa-sha2-"))
+        })
+    };
+
+    let mut preferred: Vec<&str> = supported.iter().copied().filter(|alg| is_known(alg)).collect();
+    preferred.extend(supported.iter().copied().filter(|alg| !is_known(alg)));
+    preferred
+}
+
+pub fn check_host_key(
+    path: &Path,
+    host: &str,
+    port: u16,
+    key: &[u8],
+) -> Result<HostKeyStatus, String> {
+    let name = host_entry_name(host, port);
+    let key_type = key_type_from_blob(key).unwrap_or_default();
+    let mut status = HostKeyStatus::Unknown;
+
+    for entry in read_entries(path)? {
+        match entry.marker.as_deref() {
+            Some("@revoked") => {
+                if entry.key == key {
+                    return Ok(HostKeyStatus::Revoked);
+                }
+                continue;
+            }
+            Some(_) => continue,
+            None => {}
+        }
+
+        if !patterns_match(&entry.patterns, &name) {
+            continue;
+        }
+
+        if entry.key == key {
+            status = HostKeyStatus::Trusted;
+        } else if entry.key_type == key_type && status != HostKeyStatus::Trusted {
+            status = HostKeyStatus::Changed;
+        }
+    }
+
+    Ok(status)
+}
+
+pub fn host_key_info(host: &str, port: u16, key: &[u8]) -> HostKeyInfo {
+    HostKeyInfo {
+        host: host.to_string(),
+        port,
+        key_type: key_type_from_blob(key).unwrap_or_else(|| "unknown".to_string()),
+        fingerprint: format_fingerprint(key),
+    }
+}
+
+pub fn remember_pending_key(host: &str, port: u16, key: &[u8]) -> HostKeyInfo {
+    let info = host_key_info(host, port, key);
+
+    PENDING_HOST_KEYS.lock().unwrap().insert(
+        format!("{}:{}", host, port),
+        PendingHostKey {
+            key_type: info.key_type.clone(),
+            key: key.to_vec(),
+            fingerprint: info.fingerprint.clone(),
+        },
+    );
+
+    info
+}
+
+/// A stale entry for `name` with that host taken out of its pattern list,
+/// or `None` once nothing is left; a hashed entry only ever names one host.
+/// Lines that only match through a wildcard are kept as they are: the key
+/// appended for the host wins for it, and other hosts still need the line.
+fn without_host(line: &str, name: &str) -> Option<String> {
+    let (patterns, rest) = line.trim().split_once(char::is_whitespace)?;
+    if patterns.starts_with("|1|") {
+        return None;
+    }
+
+    let remaining: Vec<&str> = patterns
+        .split(',')
+        .filter(|pattern| !pattern.eq_ignore_ascii_case(name))
+        .collect();
+    if remaining.is_empty() {
+        return None;
+    }
+    Some(format!("{} {}", remaining.join(","), rest.trim_start()))
+}
+
+/// Persists a key previously reported as unknown or changed. Existing
+/// entries for the host with the same key type are replaced; entries it
+/// shares with other hosts lose only its name.
+pub fn trust_host_key(
+    path: &Path,
+    host: &str,
+    port: u16,
+    fingerprint: &str,
+) -> Result<(), String> {
+    let pending_key = format!("{}:{}", host, port);
+    let pending = PENDING_HOST_KEYS
+        .lock()
+        .unwrap()
+        .remove(&pending_key)
+        .ok_or_else(|| format!("No pending host key for {}", pending_key))?;
+
+    if pending.fingerprint != fingerprint {
+        return Err("Host key fingerprint does not match the key presented by the server".to_string());
+    }
+
+    let name = host_entry_name(host, port);
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(format!("Failed to read known_hosts: {}", e)),
+    };
+
+    let mut kept = String::new();
+    let mut removed_any = false;
+    for line in contents.lines() {
+        let stale = parse_line(line).is_some_and(|entry| {
+            entry.marker.is_none()
+                && entry.key_type == pending.key_type
+                && patterns_match(&entry.patterns, &name)
+        });
+        let line = if stale {
+            removed_any = true;
+            without_host(line, &name)
+        } else {
+            Some(line.to_string())
+        };
+        if let Some(line) = line {
+            kept.push_str(&line);
+            kept.push('\n');
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create known_hosts directory: {}", e))?;
+    }
+
+    let new_line = format!("{} {} {}\n", name, pending.key_type, STANDARD.encode(&pending.key));
+
+    if removed_any {
+        kept.push_str(&new_line);
+        fs::write(path, kept).map_err(|e| format!("Failed to write known_hosts: {}", e))
+    } else {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| format!("Failed to open known_hosts: {}", e))?;
+        let separator = if contents.is_empty() || contents.ends_with('\n') { "" } else { "\n" };
+        file.write_all(format!("{}{}", separator, new_line).as_bytes())
+            .map_err(|e| format!("Failed to write known_hosts: {}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An SSH public key blob: the algorithm name, then `fill` as the key.
+    fn key(key_type: &str, fill: u8) -> Vec<u8> {
+        let mut blob = (key_type.len() as u32).to_be_bytes().to_vec();
+        blob.extend_from_slice(key_type.as_bytes());
+        blob.extend_from_slice(&32u32.to_be_bytes());
+        blob.extend_from_slice(&[fill; 32]);
+        blob
+    }
+
+    fn line(patterns: &str, key: &[u8]) -> String {
+        let key_type = key_type_from_blob(key).unwrap();
+        format!("{} {} {}\n", patterns, key_type, STANDARD.encode(key))
+    }
+
+    fn hashed(name: &str) -> String {
+        let salt = [7u8; 20];
+        let mut mac = Hmac::<Sha1>::new_from_slice(&salt).unwrap();
+        mac.update(name.as_bytes());
+        let hash = mac.finalize().into_bytes();
+        format!("|1|{}|{}", STANDARD.encode(salt), STANDARD.encode(hash))
+    }
+
+    fn known_hosts(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("christoph-known-hosts-{}-{}", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn status(path: &Path, host: &str, port: u16, key: &[u8]) -> HostKeyStatus {
+        check_host_key(path, host, port, key).unwrap()
+    }
+
+    #[test]
+    fn trusted_unknown_and_changed() {
+        let (ed25519, other) = (key("ssh-ed25519", 1), key("ssh-ed25519", 2));
+        let path = known_hosts("status", &line("server.example.com", &ed25519));
+
+        assert_eq!(status(&path, "server.example.com", 22, &ed25519), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "SERVER.example.com", 22, &ed25519), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "server.example.com", 22, &other), HostKeyStatus::Changed);
+        assert_eq!(status(&path, "elsewhere", 22, &ed25519), HostKeyStatus::Unknown);
+        // A different key type for a known host isn't a change, just unknown.
+        let ecdsa = key("ecdsa-sha2-nistp256", 1);
+        assert_eq!(status(&path, "server.example.com", 22, &ecdsa), HostKeyStatus::Unknown);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn revoked_keys_win_over_trusted_ones() {
+        let ed25519 = key("ssh-ed25519", 3);
+        let contents = line("host", &ed25519) + "@revoked " + &line("*", &ed25519);
+        let path = known_hosts("revoked", &contents);
+
+        assert_eq!(status(&path, "host", 22, &ed25519), HostKeyStatus::Revoked);
+        assert_eq!(status(&path, "any", 22, &ed25519), HostKeyStatus::Revoked);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn hashed_entries() {
+        let ed25519 = key("ssh-ed25519", 4);
+        let contents = line(&hashed("hidden.example.com"), &ed25519)
+            + &line(&hashed("[hidden.example.com]:2222"), &key("ssh-ed25519", 5));
+        let path = known_hosts("hashed", &contents);
+
+        assert_eq!(status(&path, "hidden.example.com", 22, &ed25519), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "hidden.example.com", 2222, &ed25519), HostKeyStatus::Changed);
+        assert_eq!(status(&path, "other.example.com", 22, &ed25519), HostKeyStatus::Unknown);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn non_default_ports_use_brackets() {
+        let ed25519 = key("ssh-ed25519", 6);
+        let path = known_hosts("ports", &line("[box]:2222", &ed25519));
+
+        assert_eq!(status(&path, "box", 2222, &ed25519), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "box", 22, &ed25519), HostKeyStatus::Unknown);
+        assert_eq!(status(&path, "box", 2200, &ed25519), HostKeyStatus::Unknown);
+        assert_eq!(host_entry_name("Box", 22), "box");
+        assert_eq!(host_entry_name("Box", 2222), "[box]:2222");
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn known_key_types_go_first() {
+        let (ed25519, ecdsa) = ("ssh-ed25519", "ecdsa-sha2-nistp256");
+        let supported = [ed25519, ecdsa, "rsa-sha2-512", "rsa-sha2-256", "ssh-rsa"];
+        assert_eq!(
+            preferred_key_types(&supported, &["ssh-rsa".to_string()]),
+            ["rsa-sha2-512", "rsa-sha2-256", "ssh-rsa", ed25519, ecdsa]
+        );
+        assert_eq!(
+            preferred_key_types(&supported, &[ecdsa.to_string()]),
+            [ecdsa, ed25519, "rsa-sha2-512", "rsa-sha2-256", "ssh-rsa"]
+        );
+        assert_eq!(preferred_key_types(&supported, &[]), supported);
+    }
+
+    #[test]
+    fn trusting_a_host_keeps_shared_lines_for_the_others() {
+        let (old, new) = (key("ssh-ed25519", 8), key("ssh-ed25519", 9));
+        let contents = line("alpha,beta,gamma", &old) + &line("*.example.com", &old);
+        let path = known_hosts("shared", &contents);
+
+        let info = remember_pending_key("beta", 22, &new);
+        trust_host_key(&path, "beta", 22, &info.fingerprint).unwrap();
+
+        assert_eq!(status(&path, "beta", 22, &new), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "beta", 22, &old), HostKeyStatus::Changed);
+        assert_eq!(status(&path, "alpha", 22, &old), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "gamma", 22, &old), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "www.example.com", 22, &old), HostKeyStatus::Trusted);
+
+        let info = remember_pending_key("www.example.com", 22, &new);
+        trust_host_key(&path, "www.example.com", 22, &info.fingerprint).unwrap();
+        assert_eq!(status(&path, "www.example.com", 22, &new), HostKeyStatus::Trusted);
+        assert_eq!(status(&path, "mail.example.com", 22, &old), HostKeyStatus::Trusted);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn trusting_replaces_single_host_and_hashed_lines() {
+        let (old, new) = (key("ssh-ed25519", 10), key("ssh-ed25519", 11));
+        let contents = line("[solo]:2222", &old) + &line(&hashed("[solo]:2222"), &old);
+        let path = known_hosts("replace", &contents);
+
+        let info = remember_pending_key("solo", 2222, &new);
+        trust_host_key(&path, "solo", 2222, &info.fingerprint).unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert_eq!(written, line("[solo]:2222", &new));
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn only_the_presented_key_can_be_trusted() {
+        let path = known_hosts("pending", "");
+        let info = remember_pending_key("pending-host", 22, &key("ssh-ed25519", 12));
+        assert!(trust_host_key(&path, "pending-host", 22, "SHA256:forged").is_err());
+        // The pending key is used up by the failed attempt.
+        assert!(trust_host_key(&path, "pending-host", 22, &info.fingerprint).is_err());
+        assert!(trust_host_key(&path, "never-seen", 22, &info.fingerprint).is_err());
+        fs::remove_file(path).ok();
+    }
+}
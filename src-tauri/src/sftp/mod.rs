@@ -1,5 +1,19 @@
+pub mod auth;
+pub mod checksum;
 pub mod client;
+pub mod conflict;
+pub mod error;
+pub mod known_hosts;
+pub mod preserve;
+pub mod progress;
+pub mod retry;
+pub mod prompt;
+pub mod ssh_config;
+pub mod throttle;
+pub mod transfer;
+pub mod tunnel;
 pub mod types;
 pub mod utils;
-pub use client::{SftpClient, cancel_transfer, start_upload, start_download};
-pub use types::{ConnectionConfig, FileInfo};
\ No newline at end of file
+pub use client::SftpClient;
+pub use error::SftpError;
+pub use types::{ConnectionConfig, ConnectionInfo, FileInfo, Secret};
\ No newline at end of file
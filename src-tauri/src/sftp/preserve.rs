@@ -0,0 +1,107 @@
+use std::fs;
+use std::path::Path;
+
+use filetime::FileTime;
+use ssh2::FileStat;
+
+use crate::sftp::{SftpClient, SftpError};
+
+const PERMISSION_BITS: u32 = 0o7777;
+
+/// Gives the remote file the local file's mtime, atime and permission bits.
+pub fn copy_to_remote(client: &SftpClient, local_path: &str, remote_path: &str) -> Result<(), SftpError> {
+    let metadata = fs::metadata(local_path).map_err(|e| SftpError::local("preserve", local_path, e))?;
+
+    let stat = FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: local_mode(&metadata),
+        atime: Some(unix_seconds(FileTime::from_last_access_time(&metadata))),
+        mtime: Some(unix_seconds(FileTime::from_last_modification_time(&metadata))),
+    };
+
+    apply_to_remote(client, remote_path, stat)
+}
+
+/// The mtime, atime and permission bits of a remote file, for handing to
+/// `apply_to_remote` on another connection.
+pub fn remote_attributes(client: &SftpClient, remote_path: &str) -> Result<FileStat, SftpError> {
+    let stat = client
+        .sftp()
+        .stat(Path::new(remote_path))
+        .map_err(|e| client.remote_error("preserve", remote_path, e))?;
+
+    Ok(FileStat {
+        size: None,
+        uid: None,
+        gid: None,
+        perm: stat.perm.map(|perm| perm & PERMISSION_BITS),
+        atime: stat.atime,
+        mtime: stat.mtime,
+    })
+}
+
+pub fn apply_to_remote(client: &SftpClient, remote_path: &str, stat: FileStat) -> Result<(), SftpError> {
+    client
+        .sftp()
+        .setstat(Path::new(remote_path), stat)
+        .map_err(|e| client.remote_error("preserve", remote_path, e))
+}
+
+/// Gives the local file the remote file's mtime, atime and permission bits.
+pub fn copy_to_local(client: &SftpClient, remote_path: &str, local_path: &str) -> Result<(), SftpError> {
+    let stat = client
+        .sftp()
+        .stat(Path::new(remote_path))
+        .map_err(|e| client.remote_error("preserve", remote_path, e))?;
+
+    if let Some(mtime) = stat.mtime {
+        let mtime = FileTime::from_unix_time(mtime as i64, 0);
+        let atime = stat
+            .atime
+            .map(|atime| FileTime::from_unix_time(atime as i64, 0))
+            .unwrap_or(mtime);
+        filetime::set_file_times(local_path, atime, mtime)
+            .map_err(|e| SftpError::local("preserve", local_path, e))?;
+    }
+
+    if let Some(perm) = stat.perm {
+        fs::set_permissions(local_path, local_permissions(local_path, perm)?)
+            .map_err(|e| SftpError::local("preserve", local_path, e))?;
+    }
+
+    Ok(())
+}
+
+fn unix_seconds(time: FileTime) -> u64 {
+    time.unix_seconds().max(0) as u64
+}
+
+#[cfg(unix)]
+fn local_mode(metadata: &fs::Metadata) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    Some(metadata.permissions().mode() & PERMISSION_BITS)
+}
+
+/// Other platforms have no mode bits to send, so the server's default stays.
+#[cfg(not(unix))]
+fn local_mode(_metadata: &fs::Metadata) -> Option<u32> {
+    None
+}
+
+#[cfg(unix)]
+fn local_permissions(_local_path: &str, perm: u32) -> Result<fs::Permissions, SftpError> {
+    use std::os::unix::fs::PermissionsExt;
+    Ok(fs::Permissions::from_mode(perm & PERMISSION_BITS))
+}
+
+/// Only the owner's write bit has a local equivalent, the read-only flag.
+#[cfg(not(unix))]
+fn local_permissions(local_path: &str, perm: u32) -> Result<fs::Permissions, SftpError> {
+    let mut permissions = fs::metadata(local_path)
+        .map_err(|e| SftpError::local("preserve", local_path, e))?
+        .permissions();
+    permissions.set_readonly(perm & 0o200 == 0);
+    Ok(permissions)
+}
@@ -0,0 +1,194 @@
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+pub const DEFAULT_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Weight of the newest sample in the smoothed rate used for the ETA.
+const RATE_SMOOTHING: f64 = 0.3;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ProgressStats {
+    pub transferred: u64,
+    pub total: u64,
+    pub elapsed_ms: u64,
+    /// Rate since the previous event.
+    pub bytes_per_second: u64,
+    /// Rate since the transfer started, not counting resumed bytes.
+    pub average_bytes_per_second: u64,
+    pub eta_seconds: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct TransferSummary {
+    pub bytes_transferred: u64,
+    pub elapsed_ms: u64,
+    pub average_bytes_per_second: u64,
+}
+
+/// Decides when a progress event is due and works out the rates for it.
+/// An event goes out when `interval` has passed or, if set, when progress
+/// has moved on by `step_percent`, whichever comes first; the first and
+/// last updates always go out.
+pub struct ProgressTracker {
+    started: Instant,
+    interval: Duration,
+    step_percent: Option<f64>,
+    start_bytes: Option<u64>,
+    last: Option<(Instant, u64)>,
+    smoothed_rate: Option<f64>,
+}
+
+impl ProgressTracker {
+    pub fn new(interval: Duration, step_percent: Option<f64>) -> Self {
+        Self {
+            started: Instant::now(),
+            interval,
+            step_percent: step_percent.filter(|step| *step > 0.0),
+            start_bytes: None,
+            last: None,
+            smoothed_rate: None,
+        }
+    }
+
+    /// Returns the stats to report if an event is due.
+    pub fn update(&mut self, transferred: u64, total: u64) -> Option<ProgressStats> {
+        let now = Instant::now();
+        let start_bytes = *self.start_bytes.get_or_insert(transferred);
+
+        let due = match self.last {
+            None => true,
+            Some(_) if total > 0 && transferred >= total => true,
+            Some((at, bytes)) => {
+                now.duration_since(at) >= self.interval
+                    || self.step_percent.is_some_and(|step| {
+                        let moved = transferred.saturating_sub(bytes) as f64;
+                        total > 0 && moved * 100.0 / total as f64 >= step
+                    })
+            }
+        };
+        if !due {
+            return None;
+        }
+
+        let bytes_per_second = match self.last {
+            Some((at, bytes)) if now > at => {
+                let seconds = now.duration_since(at).as_secs_f64();
+                let rate = transferred.saturating_sub(bytes) as f64 / seconds;
+                let smoothed = match self.smoothed_rate {
+                    Some(previous) => previous + RATE_SMOOTHING * (rate - previous),
+                    None => rate,
+                };
+                self.smoothed_rate = Some(smoothed);
+                rate as u64
+            }
+            _ => 0,
+        };
+        self.last = Some((now, transferred));
+
+        let elapsed = now.duration_since(self.started);
+        let average = rate(transferred.saturating_sub(start_bytes), elapsed);
+        let remaining = total.saturating_sub(transferred);
+        let eta_rate = self.smoothed_rate.unwrap_or(average as f64);
+        let eta_seconds = if remaining == 0 {
+            Some(0)
+        } else if eta_rate > 0.0 {
+            Some((remaining as f64 / eta_rate).ceil() as u64)
+        } else {
+            None
+        };
+
+        Some(ProgressStats {
+            transferred,
+            total,
+            elapsed_ms: elapsed.as_millis() as u64,
+            bytes_per_second,
+            average_bytes_per_second: average,
+            eta_seconds,
+        })
+    }
+
+    pub fn summary(&self) -> TransferSummary {
+        let elapsed = self.started.elapsed();
+        let bytes_transferred = match (self.start_bytes, self.last) {
+            (Some(start), Some((_, bytes))) => bytes.saturating_sub(start),
+            _ => 0,
+        };
+
+        TransferSummary {
+            bytes_transferred,
+            elapsed_ms: elapsed.as_millis() as u64,
+            average_bytes_per_second: rate(bytes_transferred, elapsed),
+        }
+    }
+}
+
+fn rate(bytes: u64, elapsed: Duration) -> u64 {
+    let seconds = elapsed.as_secs_f64();
+    if seconds > 0.0 {
+        (bytes as f64 / seconds) as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn first_and_last_updates_always_go_out() {
+        let mut tracker = ProgressTracker::new(Duration::from_secs(60), None);
+        assert!(tracker.update(0, 1_000).is_some());
+        assert!(tracker.update(500, 1_000).is_none());
+        let last = tracker.update(1_000, 1_000).unwrap();
+        assert_eq!(last.eta_seconds, Some(0));
+    }
+
+    #[test]
+    fn step_percent_triggers_before_the_interval() {
+        let mut tracker = ProgressTracker::new(Duration::from_secs(60), Some(10.0));
+        tracker.update(0, 1_000);
+        assert!(tracker.update(99, 1_000).is_none());
+        assert!(tracker.update(100, 1_000).is_some());
+        assert!(tracker.update(150, 1_000).is_none());
+    }
+
+    #[test]
+    fn a_zero_step_only_uses_the_interval() {
+        let mut tracker = ProgressTracker::new(Duration::from_secs(60), Some(0.0));
+        tracker.update(0, 1_000);
+        assert!(tracker.update(999, 1_000).is_none());
+    }
+
+    #[test]
+    fn reports_rates_and_an_eta() {
+        let mut tracker = ProgressTracker::new(Duration::ZERO, None);
+        let first = tracker.update(0, 1_000_000).unwrap();
+        assert_eq!(first.bytes_per_second, 0);
+
+        thread::sleep(Duration::from_millis(20));
+        let stats = tracker.update(100_000, 1_000_000).unwrap();
+        assert!(stats.bytes_per_second > 0);
+        assert!(stats.average_bytes_per_second > 0);
+        assert!(stats.eta_seconds.is_some_and(|eta| eta > 0));
+    }
+
+    #[test]
+    fn resumed_bytes_are_left_out_of_the_summary() {
+        let mut tracker = ProgressTracker::new(Duration::ZERO, None);
+        tracker.update(4_000, 10_000);
+        tracker.update(10_000, 10_000);
+        assert_eq!(tracker.summary().bytes_transferred, 6_000);
+    }
+
+    #[test]
+    fn unknown_totals_have_no_eta_until_data_moves() {
+        let mut tracker = ProgressTracker::new(Duration::ZERO, Some(5.0));
+        let stats = tracker.update(0, 0).unwrap();
+        assert_eq!(stats.eta_seconds, Some(0));
+        assert_eq!(tracker.summary().bytes_transferred, 0);
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Sender};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use ssh2::{KeyboardInteractivePrompt, Prompt};
+use tauri::{Emitter, Window};
+use uuid::Uuid;
+
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(300);
+
+type PromptSender = Sender<Option<Vec<String>>>;
+
+static PENDING_PROMPTS: Lazy<Mutex<HashMap<String, PromptSender>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Relays keyboard-interactive challenges to the frontend as `auth_prompt`
+/// events and blocks until `respond_to_prompt` delivers the answers.
+pub struct WindowPrompter {
+    window: Window,
+    host: String,
+    cancelled: bool,
+}
+
+impl WindowPrompter {
+    pub fn new(window: Window, host: &str) -> Self {
+        Self {
+            window,
+            host: host.to_string(),
+            cancelled: false,
+        }
+    }
+
+    pub fn cancelled(&self) -> bool {
+        self.cancelled
+    }
+}
+
+impl KeyboardInteractivePrompt for WindowPrompter {
+    fn prompt<'a>(
+        &mut self,
+        username: &str,
+        instructions: &str,
+        prompts: &[Prompt<'a>],
+    ) -> Vec<String> {
+        if self.cancelled {
+            return Vec::new();
+        }
+
+        let prompt_id = Uuid::new_v4().to_string();
+        let (tx, rx) = mpsc::channel();
+        PENDING_PROMPTS.lock().unwrap().insert(prompt_id.clone(), tx);
+
+        let prompts: Vec<_> = prompts
+            .iter()
+            .map(|p| serde_json::json!({ "text": p.text, "echo": p.echo }))
+            .collect();
+
+        self.window.emit(
+            "auth_prompt",
+            serde_json::json!({
+                "prompt_id": prompt_id,
+                "host": self.host,
+                "username": username,
+                "instructions": instructions,
+                "prompts": prompts
+            }),
+        ).ok();
+
+        let answer = rx.recv_timeout(PROMPT_TIMEOUT).ok().flatten();
+        PENDING_PROMPTS.lock().unwrap().remove(&prompt_id);
+
+        match answer {
+            Some(responses) => responses,
+            None => {
+                self.cancelled = true;
+                Vec::new()
+            }
+        }
+    }
+}
+
+/// Delivers the user's answers for a pending prompt; `None` cancels it.
+pub fn respond_to_prompt(prompt_id: &str, responses: Option<Vec<String>>) -> Result<(), String> {
+    let sender = PENDING_PROMPTS
+        .lock()
+        .unwrap()
+        .remove(prompt_id)
+        .ok_or_else(|| "Prompt not found".to_string())?;
+
+    sender
+        .send(responses)
+        .map_err(|_| "Prompt is no longer waiting for an answer".to_string())
+}
@@ -0,0 +1,125 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::sftp::SftpError;
+
+/// Kinds of failure a transfer may be retried after.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryClass {
+    /// The session dropped or the connection went away.
+    ConnectionLost,
+    Timeout,
+    /// The server refused or failed a request for a reason it didn't name.
+    RemoteFailure,
+    LocalIo,
+}
+
+/// When and how often a failed transfer is tried again. Attempts after the
+/// first resume from what already made it across.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// Including the first try, so 1 disables retrying.
+    pub max_attempts: u32,
+    pub initial_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub multiplier: f64,
+    pub retry_on: Vec<RetryClass>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay_ms: 2_000,
+            max_delay_ms: 60_000,
+            multiplier: 2.0,
+            retry_on: vec![RetryClass::ConnectionLost, RetryClass::Timeout],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether attempt number `attempt` (starting at 1) failing with `error`
+    /// should be followed by another.
+    pub fn should_retry(&self, error: &SftpError, attempt: u32) -> bool {
+        attempt < self.max_attempts && self.retries(error)
+    }
+
+    /// Whether `error` is of a kind this policy retries, whatever the
+    /// attempt count.
+    pub fn retries(&self, error: &SftpError) -> bool {
+        classify(error).is_some_and(|class| self.retry_on.contains(&class))
+    }
+
+    /// How long to wait after attempt number `attempt` failed.
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let factor = self.multiplier.max(1.0).powi(exponent);
+        let delay_ms = (self.initial_delay_ms as f64 * factor).min(self.max_delay_ms as f64);
+        Duration::from_millis(delay_ms as u64)
+    }
+}
+
+fn classify(error: &SftpError) -> Option<RetryClass> {
+    match error {
+        SftpError::ConnectionLost { .. } | SftpError::ConnectionNotFound { .. } => {
+            Some(RetryClass::ConnectionLost)
+        }
+        SftpError::Timeout { .. } => Some(RetryClass::Timeout),
+        SftpError::Failure { .. } => Some(RetryClass::RemoteFailure),
+        SftpError::LocalIo { .. } => Some(RetryClass::LocalIo),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeout() -> SftpError {
+        SftpError::Timeout {
+            operation: "upload".to_string(),
+            target: "/file".to_string(),
+            seconds: 30,
+        }
+    }
+
+    #[test]
+    fn delay_grows_by_the_multiplier_up_to_the_cap() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1), Duration::from_secs(2));
+        assert_eq!(policy.delay(2), Duration::from_secs(4));
+        assert_eq!(policy.delay(3), Duration::from_secs(8));
+        assert_eq!(policy.delay(10), Duration::from_secs(60));
+        assert_eq!(policy.delay(u32::MAX), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn delay_never_shrinks() {
+        let policy = RetryPolicy {
+            multiplier: 0.5,
+            ..RetryPolicy::default()
+        };
+        assert_eq!(policy.delay(0), Duration::from_secs(2));
+        assert_eq!(policy.delay(5), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn retries_only_listed_classes_within_the_attempt_limit() {
+        let policy = RetryPolicy::default();
+        assert!(policy.should_retry(&timeout(), 1));
+        assert!(policy.should_retry(&timeout(), 2));
+        assert!(!policy.should_retry(&timeout(), 3));
+
+        let local = SftpError::LocalIo {
+            operation: "download".to_string(),
+            path: "/tmp/file".to_string(),
+            message: "disk full".to_string(),
+        };
+        assert!(!policy.should_retry(&local, 1));
+        assert!(!policy.should_retry(&SftpError::cancelled("upload", "/file"), 1));
+    }
+}
@@ -0,0 +1,525 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::sftp::utils::{match_pattern_list, wildcard_match};
+use crate::sftp::ConnectionConfig;
+
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MAX_JUMP_DEPTH: usize = 8;
+const SYSTEM_CONFIG_PATH: &str = "/etc/ssh/ssh_config";
+
+/// Settings collected for one host. As in OpenSSH, the first value seen for
+/// a keyword wins, except `IdentityFile`, which accumulates.
+#[derive(Default)]
+struct HostSettings {
+    hostname: Option<String>,
+    port: Option<u16>,
+    user: Option<String>,
+    identity_files: Vec<String>,
+    proxy_jump: Option<String>,
+    known_hosts_file: Option<String>,
+}
+
+pub fn config_paths() -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    if let Some(home) = dirs::home_dir() {
+        paths.push(home.join(".ssh").join("config"));
+    }
+    paths.push(PathBuf::from(SYSTEM_CONFIG_PATH));
+    paths.into_iter().filter(|path| path.is_file()).collect()
+}
+
+/// Host aliases that can be resolved, i.e. every literal `Host` pattern.
+pub fn list_host_aliases() -> Result<Vec<String>, String> {
+    let mut aliases = Vec::new();
+    for path in config_paths() {
+        collect_aliases(&path, &include_base(&path), 0, &mut aliases)?;
+    }
+    aliases.sort();
+    aliases.dedup();
+    Ok(aliases)
+}
+
+/// Builds a `ConnectionConfig` for `alias` from the user and system configs,
+/// including any `ProxyJump` hosts, which are resolved the same way.
+pub fn resolve_host(alias: &str) -> Result<ConnectionConfig, String> {
+    resolve_host_depth(alias, 0)
+}
+
+fn resolve_host_depth(alias: &str, depth: usize) -> Result<ConnectionConfig, String> {
+    if depth > MAX_JUMP_DEPTH {
+        return Err(format!("Too many nested ProxyJump hosts resolving {}", alias));
+    }
+
+    let mut settings = HostSettings::default();
+    for path in config_paths() {
+        apply_file(&path, &include_base(&path), alias, &mut settings, true, 0)?;
+    }
+
+    let host = settings
+        .hostname
+        .as_deref()
+        .map(|hostname| expand_tokens(hostname, alias, alias, &settings))
+        .unwrap_or_else(|| alias.to_string());
+
+    let identity_files: Vec<String> = settings
+        .identity_files
+        .iter()
+        .map(|file| expand_tokens(file, alias, &host, &settings))
+        .collect();
+    let private_key_path = identity_files
+        .iter()
+        .find(|file| Path::new(file).is_file())
+        .or(identity_files.first())
+        .cloned();
+
+    let mut jump_hosts = Vec::new();
+    if let Some(proxy_jump) = settings.proxy_jump.as_deref() {
+        if !proxy_jump.eq_ignore_ascii_case("none") {
+            for jump in proxy_jump.split(',') {
+                let (user, jump_alias, port) = parse_jump(jump.trim());
+                let mut jump_config = resolve_host_depth(jump_alias, depth + 1)?;
+                if let Some(user) = user {
+                    jump_config.username = user.to_string();
+                }
+                if let Some(port) = port {
+                    jump_config.port = port;
+                }
+                // A jump host's own jumps come before it in the chain.
+                jump_hosts.append(&mut jump_config.jump_hosts);
+                jump_hosts.push(jump_config);
+            }
+        }
+    }
+
+    let known_hosts_path = settings
+        .known_hosts_file
+        .as_deref()
+        .map(|file| expand_tokens(file, alias, &host, &settings));
+
+    Ok(ConnectionConfig {
+        host,
+        port: settings.port.unwrap_or(22),
+        username: settings.user.clone().unwrap_or_else(local_username),
+        private_key_path,
+        known_hosts_path,
+        use_agent: std::env::var_os("SSH_AUTH_SOCK").is_some(),
+        jump_hosts,
+        ..Default::default()
+    })
+}
+
+fn apply_file(
+    path: &Path,
+    include_base: &Path,
+    alias: &str,
+    settings: &mut HostSettings,
+    mut active: bool,
+    depth: usize,
+) -> Result<(), String> {
+    let contents = read_config(path, depth)?;
+
+    for line in contents.lines() {
+        let Some((keyword, args)) = parse_line(line) else {
+            continue;
+        };
+
+        match keyword.as_str() {
+            "host" => active = match_pattern_list(args.iter().map(String::as_str), alias),
+            "match" => active = match_criteria(&args, alias, settings),
+            "include" if active => {
+                for include in expand_include(&args, include_base) {
+                    apply_file(&include, include_base, alias, settings, true, depth + 1)?;
+                }
+            }
+            _ if active => apply_keyword(&keyword, &args, settings),
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn apply_keyword(keyword: &str, args: &[String], settings: &mut HostSettings) {
+    let Some(value) = args.first() else {
+        return;
+    };
+
+    match keyword {
+        "hostname" if settings.hostname.is_none() => settings.hostname = Some(value.clone()),
+        "port" if settings.port.is_none() => settings.port = value.parse().ok(),
+        "user" if settings.user.is_none() => settings.user = Some(value.clone()),
+        "identityfile" if !settings.identity_files.contains(value) => {
+            settings.identity_files.push(value.clone())
+        }
+        "proxyjump" if settings.proxy_jump.is_none() => settings.proxy_jump = Some(value.clone()),
+        "userknownhostsfile" if settings.known_hosts_file.is_none() => {
+            settings.known_hosts_file = Some(value.clone())
+        }
+        _ => {}
+    }
+}
+
+/// Evaluates a `Match` line. Criteria we can't evaluate (e.g. `exec`) never
+/// match, so their blocks are skipped rather than applied blindly.
+fn match_criteria(args: &[String], alias: &str, settings: &HostSettings) -> bool {
+    let mut args = args.iter();
+    while let Some(criterion) = args.next() {
+        let lowered = criterion.to_lowercase();
+        let (negated, name) = match lowered.strip_prefix('!') {
+            Some(name) => (true, name),
+            None => (false, lowered.as_str()),
+        };
+
+        let matched = match name {
+            "all" => true,
+            "canonical" | "final" => false,
+            "host" | "originalhost" | "user" | "localuser" => {
+                let Some(list) = args.next() else {
+                    return false;
+                };
+                let subject = match name {
+                    "host" => settings.hostname.clone().unwrap_or_else(|| alias.to_string()),
+                    "originalhost" => alias.to_string(),
+                    "user" => settings.user.clone().unwrap_or_else(local_username),
+                    _ => local_username(),
+                };
+                match_pattern_list(list.split(','), &subject)
+            }
+            _ => {
+                args.next();
+                false
+            }
+        };
+
+        if matched == negated {
+            return false;
+        }
+    }
+    true
+}
+
+fn collect_aliases(
+    path: &Path,
+    include_base: &Path,
+    depth: usize,
+    aliases: &mut Vec<String>,
+) -> Result<(), String> {
+    let contents = read_config(path, depth)?;
+
+    for line in contents.lines() {
+        let Some((keyword, args)) = parse_line(line) else {
+            continue;
+        };
+
+        match keyword.as_str() {
+            "host" => aliases.extend(
+                args.into_iter()
+                    .filter(|pattern| !pattern.contains(['*', '?', '!'])),
+            ),
+            "include" => {
+                for include in expand_include(&args, include_base) {
+                    collect_aliases(&include, include_base, depth + 1, aliases)?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn read_config(path: &Path, depth: usize) -> Result<String, String> {
+    if depth > MAX_INCLUDE_DEPTH {
+        return Err(format!("Include nested too deeply at {}", path.display()));
+    }
+    fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+}
+
+/// Splits a config line into a lowercased keyword and its arguments.
+/// Keywords may be separated from arguments by whitespace or `=`, and
+/// arguments may be double-quoted.
+fn parse_line(line: &str) -> Option<(String, Vec<String>)> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+
+    let split_at = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let keyword = line[..split_at].to_lowercase();
+    let rest = line[split_at..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest);
+
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut has_token = false;
+    for c in rest.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                has_token = true;
+            }
+            '#' if !in_quotes && !has_token => break,
+            c if c.is_whitespace() && !in_quotes => {
+                if has_token {
+                    args.push(std::mem::take(&mut current));
+                    has_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                has_token = true;
+            }
+        }
+    }
+    if has_token {
+        args.push(current);
+    }
+
+    Some((keyword, args))
+}
+
+/// Where relative `Include` paths in a top-level config resolve: `~/.ssh`
+/// for the user's config, `/etc/ssh` for the system one. Files pulled in by
+/// an `Include` keep the base of the config that included them.
+fn include_base(config_path: &Path) -> PathBuf {
+    config_path.parent().map(Path::to_path_buf).unwrap_or_default()
+}
+
+/// Include paths are relative to `base` and may use globs in the file name.
+fn expand_include(args: &[String], base: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for arg in args {
+        let path = PathBuf::from(expand_home(arg));
+        let path = if path.is_absolute() { path } else { base.join(path) };
+
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+
+        if !file_name.contains(['*', '?']) {
+            if path.is_file() {
+                paths.push(path);
+            }
+            continue;
+        }
+
+        let Some(parent) = path.parent() else {
+            continue;
+        };
+        let Ok(entries) = fs::read_dir(parent) else {
+            continue;
+        };
+        let mut matches: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|candidate| {
+                candidate.is_file()
+                    && candidate
+                        .file_name()
+                        .is_some_and(|name| wildcard_match(&file_name, &name.to_string_lossy()))
+            })
+            .collect();
+        matches.sort();
+        paths.extend(matches);
+    }
+    paths
+}
+
+/// `[user@]host[:port]`, optionally as an `ssh://` URI.
+fn parse_jump(jump: &str) -> (Option<&str>, &str, Option<u16>) {
+    let jump = jump.strip_prefix("ssh://").unwrap_or(jump);
+    let (user, host_port) = match jump.rsplit_once('@') {
+        Some((user, rest)) => (Some(user), rest),
+        None => (None, jump),
+    };
+
+    if let Some(bracketed) = host_port.strip_prefix('[') {
+        if let Some((host, rest)) = bracketed.split_once(']') {
+            let port = rest.strip_prefix(':').and_then(|port| port.parse().ok());
+            return (user, host, port);
+        }
+    }
+
+    match host_port.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => (user, host, port.parse().ok()),
+        _ => (user, host_port, None),
+    }
+}
+
+fn expand_tokens(value: &str, alias: &str, hostname: &str, settings: &HostSettings) -> String {
+    let home = dirs::home_dir()
+        .map(|home| home.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut expanded = String::new();
+    let mut chars = expand_home(value).chars().collect::<Vec<_>>().into_iter();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some('h') => expanded.push_str(hostname),
+            Some('n') => expanded.push_str(alias),
+            Some('p') => expanded.push_str(&settings.port.unwrap_or(22).to_string()),
+            Some('r') => expanded.push_str(&settings.user.clone().unwrap_or_else(local_username)),
+            Some('u') => expanded.push_str(&local_username()),
+            Some('d') => expanded.push_str(&home),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+fn expand_home(value: &str) -> String {
+    match (value.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest).to_string_lossy().to_string(),
+        _ => value.to_string(),
+    }
+}
+
+fn local_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temp dir for one test's configs.
+    fn config_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir()
+            .join(format!("christoph-ssh-config-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn settings_for(config: &Path, alias: &str) -> HostSettings {
+        let mut settings = HostSettings::default();
+        apply_file(config, &include_base(config), alias, &mut settings, true, 0).unwrap();
+        settings
+    }
+
+    #[test]
+    fn parses_keywords_with_equals_and_quotes() {
+        assert_eq!(
+            parse_line("  IdentityFile=\"~/my keys/id\" # comment"),
+            Some(("identityfile".to_string(), vec!["~/my keys/id".to_string()]))
+        );
+        assert_eq!(
+            parse_line("Host a b"),
+            Some(("host".to_string(), vec!["a".to_string(), "b".to_string()]))
+        );
+        assert_eq!(parse_line("# just a comment"), None);
+        assert_eq!(parse_line("   "), None);
+    }
+
+    #[test]
+    fn host_patterns_and_negation() {
+        let dir = config_dir("host-patterns");
+        let config = dir.join("config");
+        fs::write(
+            &config,
+            "Host *.example.com !bastion.example.com\n  User web\n\
+             Host db?\n  Port 2200\n",
+        )
+        .unwrap();
+
+        assert_eq!(settings_for(&config, "www.example.com").user.as_deref(), Some("web"));
+        assert_eq!(settings_for(&config, "bastion.example.com").user, None);
+        assert_eq!(settings_for(&config, "db1").port, Some(2200));
+        assert_eq!(settings_for(&config, "db10").port, None);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn first_value_wins_except_identity_files() {
+        let dir = config_dir("first-wins");
+        let config = dir.join("config");
+        fs::write(
+            &config,
+            "Host server\n  HostName one.example.com\n  IdentityFile /keys/a\n\
+             Host *\n  HostName two.example.com\n  IdentityFile /keys/b\n  User fallback\n",
+        )
+        .unwrap();
+
+        let settings = settings_for(&config, "server");
+        assert_eq!(settings.hostname.as_deref(), Some("one.example.com"));
+        assert_eq!(settings.user.as_deref(), Some("fallback"));
+        assert_eq!(settings.identity_files, ["/keys/a", "/keys/b"]);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn match_criteria_and_negation() {
+        let dir = config_dir("match");
+        let config = dir.join("config");
+        fs::write(
+            &config,
+            "Host short\n  HostName short.internal\n\
+             Match host *.internal !originalhost other\n  Port 2022\n\
+             Match exec \"true\"\n  User never\n",
+        )
+        .unwrap();
+
+        let settings = settings_for(&config, "short");
+        assert_eq!(settings.port, Some(2022));
+        assert_eq!(settings.user, None);
+        assert_eq!(settings_for(&config, "other").port, None);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn includes_resolve_against_the_top_level_config() {
+        let dir = config_dir("include-base");
+        fs::create_dir_all(dir.join("conf.d")).unwrap();
+        fs::write(dir.join("config"), "Include conf.d/*.conf\n").unwrap();
+        // Nested includes still resolve against the top-level config's directory.
+        fs::write(dir.join("conf.d/10-a.conf"), "Include extra\nHost a\n  Port 1\n").unwrap();
+        fs::write(dir.join("conf.d/20-b.conf"), "Host b\n  Port 2\n").unwrap();
+        fs::write(dir.join("extra"), "Host c\n  Port 3\n").unwrap();
+
+        let config = dir.join("config");
+        assert_eq!(settings_for(&config, "a").port, Some(1));
+        assert_eq!(settings_for(&config, "b").port, Some(2));
+        assert_eq!(settings_for(&config, "c").port, Some(3));
+
+        let mut aliases = Vec::new();
+        collect_aliases(&config, &include_base(&config), 0, &mut aliases).unwrap();
+        aliases.sort();
+        assert_eq!(aliases, ["a", "b", "c"]);
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn include_depth_is_limited() {
+        let dir = config_dir("include-depth");
+        let config = dir.join("config");
+        fs::write(&config, "Include config\n").unwrap();
+
+        let mut settings = HostSettings::default();
+        let result = apply_file(&config, &dir, "any", &mut settings, true, 0);
+        assert!(result.unwrap_err().contains("nested too deeply"));
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn parses_jump_specs() {
+        assert_eq!(parse_jump("bastion"), (None, "bastion", None));
+        assert_eq!(parse_jump("me@bastion:2222"), (Some("me"), "bastion", Some(2222)));
+        assert_eq!(parse_jump("ssh://me@[::1]:22"), (Some("me"), "::1", Some(22)));
+        assert_eq!(parse_jump("fe80::1"), (None, "fe80::1", None));
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use once_cell::sync::Lazy;
+
+/// Longest single sleep while waiting for tokens, so a changed rate takes
+/// effect promptly even for a transfer that is deep in debt.
+const MAX_WAIT: Duration = Duration::from_millis(100);
+
+/// Applies to every transfer on top of their own limits.
+pub static GLOBAL_RATE_LIMIT: Lazy<RateLimiter> = Lazy::new(|| RateLimiter::new(None));
+
+/// A token bucket measured in bytes per second. The bucket holds at most
+/// one second's worth, so an idle transfer can't save up a large burst.
+pub struct RateLimiter {
+    bucket: Mutex<Bucket>,
+}
+
+struct Bucket {
+    rate: Option<u64>,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self) {
+        let now = Instant::now();
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.updated).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        }
+        self.updated = now;
+    }
+}
+
+impl RateLimiter {
+    /// `None` or 0 means unlimited.
+    pub fn new(rate: Option<u64>) -> Self {
+        Self {
+            bucket: Mutex::new(Bucket {
+                rate: rate.filter(|rate| *rate > 0),
+                tokens: 0.0,
+                updated: Instant::now(),
+            }),
+        }
+    }
+
+    pub fn rate(&self) -> Option<u64> {
+        self.bucket.lock().unwrap().rate
+    }
+
+    /// Takes effect immediately, including for transfers already waiting.
+    pub fn set_rate(&self, rate: Option<u64>) {
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill();
+        bucket.rate = rate.filter(|rate| *rate > 0);
+        bucket.tokens = match bucket.rate {
+            Some(rate) => bucket.tokens.min(rate as f64),
+            None => 0.0,
+        };
+    }
+
+    /// Blocks until `bytes` may pass. Chunks larger than the bucket are let
+    /// through by going into debt, which later calls wait off.
+    ///
+    /// `interrupted` is checked between sleeps. Once it returns true this
+    /// gives up and returns false, leaving the debt in place; acquiring 0
+    /// bytes later waits off whatever is left.
+    pub fn acquire(&self, bytes: u64, interrupted: &dyn Fn() -> bool) -> bool {
+        {
+            let mut bucket = self.bucket.lock().unwrap();
+            if bucket.rate.is_none() {
+                return true;
+            }
+            bucket.refill();
+            bucket.tokens -= bytes as f64;
+        }
+
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill();
+                match bucket.rate {
+                    Some(rate) if bucket.tokens < 0.0 => {
+                        Duration::from_secs_f64(-bucket.tokens / rate as f64)
+                    }
+                    _ => return true,
+                }
+            };
+            if interrupted() {
+                return false;
+            }
+            thread::sleep(wait.min(MAX_WAIT));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[test]
+    fn unlimited_never_waits() {
+        for limiter in [RateLimiter::new(None), RateLimiter::new(Some(0))] {
+            assert_eq!(limiter.rate(), None);
+            let start = Instant::now();
+            assert!(limiter.acquire(u64::MAX / 2, &|| true));
+            assert!(start.elapsed() < Duration::from_millis(50));
+        }
+    }
+
+    #[test]
+    fn waits_off_debt_at_the_set_rate() {
+        let limiter = RateLimiter::new(Some(100_000));
+        let start = Instant::now();
+        assert!(limiter.acquire(20_000, &|| false));
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(150), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(1), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn idle_time_saves_up_at_most_one_second() {
+        let limiter = RateLimiter::new(Some(1_000));
+        {
+            let mut bucket = limiter.bucket.lock().unwrap();
+            bucket.updated -= Duration::from_secs(5);
+            bucket.refill();
+            assert_eq!(bucket.tokens, 1_000.0);
+        }
+
+        limiter.set_rate(Some(10));
+        assert_eq!(limiter.bucket.lock().unwrap().tokens, 10.0);
+    }
+
+    #[test]
+    fn lifting_the_limit_releases_a_waiting_transfer() {
+        let limiter = Arc::new(RateLimiter::new(Some(1_000)));
+        let waiting = {
+            let limiter = limiter.clone();
+            thread::spawn(move || limiter.acquire(1_000_000, &|| false))
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        limiter.set_rate(None);
+        assert!(waiting.join().unwrap());
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert_eq!(limiter.rate(), None);
+    }
+
+    #[test]
+    fn an_interrupted_wait_keeps_its_debt() {
+        let limiter = Arc::new(RateLimiter::new(Some(1_000)));
+        let stop = Arc::new(AtomicBool::new(false));
+        let waiting = {
+            let (limiter, stop) = (limiter.clone(), stop.clone());
+            thread::spawn(move || limiter.acquire(1_000_000, &|| stop.load(Ordering::Relaxed)))
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        stop.store(true, Ordering::Relaxed);
+        assert!(!waiting.join().unwrap());
+        assert!(start.elapsed() < Duration::from_millis(500));
+        assert!(limiter.bucket.lock().unwrap().tokens < -900_000.0);
+    }
+}
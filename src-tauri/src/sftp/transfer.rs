@@ -0,0 +1,1504 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use ssh2::{OpenFlags, OpenType};
+use tauri::{Emitter, Window};
+use uuid::Uuid;
+
+use crate::sftp::checksum::{self, Verification};
+use crate::sftp::conflict::{
+    self, Conflict, ConflictDecisions, ConflictPolicy, ConflictResolution, FileSummary,
+};
+use crate::sftp::preserve;
+use crate::sftp::progress::{ProgressTracker, DEFAULT_PROGRESS_INTERVAL};
+use crate::sftp::retry::RetryPolicy;
+use crate::sftp::throttle::{RateLimiter, GLOBAL_RATE_LIMIT};
+use crate::sftp::utils::shell_quote;
+use crate::sftp::error::FileError;
+use crate::sftp::{SftpClient, SftpError};
+
+/// How much of the already-transferred data is compared before resuming.
+const RESUME_VERIFY_LEN: u64 = 64 * 1024;
+
+/// Copies between connections move data in chunks of this size, with at
+/// most `PIPE_DEPTH` of them read ahead of the writer.
+const PIPE_CHUNK: usize = 32 * 1024;
+const PIPE_DEPTH: usize = 8;
+
+type PipeChunk = Result<Vec<u8>, SftpError>;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferDirection {
+    Upload,
+    Download,
+    /// From one connection to another, without going through local disk.
+    Copy,
+}
+
+impl TransferDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TransferDirection::Upload => "upload",
+            TransferDirection::Download => "download",
+            TransferDirection::Copy => "copy",
+        }
+    }
+}
+
+/// Everything needed to run a transfer again. Sent along with
+/// `transfer_error` and `transfer_cancelled` so the frontend can hand it
+/// back to `retry_transfer`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TransferRecord {
+    pub transfer_id: String,
+    pub connection_id: String,
+    pub direction: TransferDirection,
+    pub local_path: String,
+    pub remote_path: String,
+    /// Only set for copies. `connection_id` and `remote_path` are then the
+    /// source, and `local_path` is where the copy goes on this connection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target_connection_id: Option<String>,
+    #[serde(default)]
+    pub options: TransferOptions,
+    /// Where an earlier attempt sent each destination it got to, `None` if
+    /// it was skipped. A retry writes to the same places without applying
+    /// the conflict policy again, so its own partial files are resumed.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub resolved: HashMap<String, Option<String>>,
+}
+
+/// Per-transfer settings chosen when the transfer is started.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct TransferOptions {
+    /// Bytes per second; `None` or 0 means unlimited. Can be changed while
+    /// the transfer runs.
+    pub rate_limit: Option<u64>,
+    /// Compare SHA-256 checksums of both copies once a file is done.
+    pub verify: bool,
+    /// Upload into a temporary file next to the target and rename it into
+    /// place once complete, so the target is never seen half-written.
+    pub atomic: bool,
+    /// Ask the server to flush uploaded files to disk before closing them,
+    /// where it supports `fsync@openssh.com`.
+    pub fsync: bool,
+    /// Carry modification/access times and permission bits over to the
+    /// copy, directories included.
+    pub preserve: bool,
+    /// Minimum time between progress events; 250 ms when not set.
+    pub progress_interval_ms: Option<u64>,
+    /// Also send a progress event whenever this many percent more is done.
+    pub progress_step_percent: Option<f64>,
+    pub retry: RetryPolicy,
+    /// What to do when a destination file already exists.
+    pub conflict: ConflictPolicy,
+}
+
+impl TransferOptions {
+    fn progress_tracker(&self) -> ProgressTracker {
+        let interval = self
+            .progress_interval_ms
+            .map(Duration::from_millis)
+            .unwrap_or(DEFAULT_PROGRESS_INTERVAL);
+        ProgressTracker::new(interval, self.progress_step_percent)
+    }
+}
+
+impl TransferRecord {
+    pub fn new(
+        connection_id: String,
+        direction: TransferDirection,
+        local_path: String,
+        remote_path: String,
+        options: TransferOptions,
+    ) -> Self {
+        Self {
+            transfer_id: Uuid::new_v4().to_string(),
+            connection_id,
+            direction,
+            local_path,
+            remote_path,
+            target_connection_id: None,
+            options,
+            resolved: HashMap::new(),
+        }
+    }
+
+    pub fn copy(
+        source_connection_id: String,
+        source_path: String,
+        target_connection_id: String,
+        target_path: String,
+        options: TransferOptions,
+    ) -> Self {
+        Self {
+            target_connection_id: Some(target_connection_id),
+            ..Self::new(
+                source_connection_id,
+                TransferDirection::Copy,
+                target_path,
+                source_path,
+                options,
+            )
+        }
+    }
+
+    /// Whether the transfer reads from or writes to `connection_id`.
+    pub fn uses_connection(&self, connection_id: &str) -> bool {
+        self.connection_id == connection_id
+            || self.target_connection_id.as_deref() == Some(connection_id)
+    }
+}
+
+struct PlannedDirectory {
+    local_path: String,
+    remote_path: String,
+}
+
+struct PlannedFile {
+    local_path: String,
+    remote_path: String,
+    size: u64,
+}
+
+/// The directories to create on the destination side, parents first, and
+/// the files to copy into them.
+#[derive(Default)]
+struct TransferPlan {
+    directories: Vec<PlannedDirectory>,
+    files: Vec<PlannedFile>,
+}
+
+/// Lets the queue pause, resume, cancel and throttle a transfer while it
+/// runs.
+pub struct TransferControl {
+    cancelled: AtomicBool,
+    paused: Mutex<bool>,
+    changed: Condvar,
+    rate_limit: RateLimiter,
+    transferred: AtomicU64,
+    conflicts: Mutex<ConflictDecisions>,
+}
+
+impl TransferControl {
+    /// Starts from the conflict decisions `record` carries over from an
+    /// earlier run.
+    pub fn new(record: &TransferRecord) -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+            paused: Mutex::new(false),
+            changed: Condvar::new(),
+            rate_limit: RateLimiter::new(record.options.rate_limit),
+            transferred: AtomicU64::new(0),
+            conflicts: Mutex::new(ConflictDecisions::from(record.resolved.clone())),
+        }
+    }
+
+    /// `record` with the conflict decisions made so far, for handing back
+    /// to `retry_transfer`.
+    pub fn resumable(&self, record: &TransferRecord) -> TransferRecord {
+        TransferRecord {
+            resolved: self.conflicts.lock().unwrap().decided().clone(),
+            ..record.clone()
+        }
+    }
+
+    pub fn rate_limit(&self) -> Option<u64> {
+        self.rate_limit.rate()
+    }
+
+    pub fn set_rate_limit(&self, rate_limit: Option<u64>) {
+        self.rate_limit.set_rate(rate_limit);
+    }
+
+    /// Bytes copied so far across every attempt, not counting what a resume
+    /// skipped over.
+    pub fn bytes_transferred(&self) -> u64 {
+        self.transferred.load(Ordering::Relaxed)
+    }
+
+    pub fn pause(&self) {
+        *self.paused.lock().unwrap() = true;
+    }
+
+    pub fn resume(&self) {
+        *self.paused.lock().unwrap() = false;
+        self.changed.notify_all();
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+        let _paused = self.paused.lock().unwrap();
+        self.changed.notify_all();
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Sleeps for `duration` unless cancelled first. Returns false if the
+    /// transfer was cancelled.
+    pub fn wait(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        let mut paused = self.paused.lock().unwrap();
+        while !self.is_cancelled() {
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            paused = self.changed.wait_timeout(paused, deadline - now).unwrap().0;
+        }
+        false
+    }
+
+    /// Waits until both this transfer's limit and the global one allow
+    /// another `bytes`. A pause holds the wait where it is until resumed.
+    /// Returns false if the transfer was cancelled.
+    fn throttle(&self, bytes: u64) -> bool {
+        self.transferred.fetch_add(bytes, Ordering::Relaxed);
+        let interrupted = || self.is_cancelled() || *self.paused.lock().unwrap();
+
+        for limiter in [&self.rate_limit, &*GLOBAL_RATE_LIMIT] {
+            let mut bytes = bytes;
+            while !limiter.acquire(bytes, &interrupted) {
+                self.checkpoint();
+                if self.is_cancelled() {
+                    return false;
+                }
+                bytes = 0;
+            }
+        }
+        true
+    }
+
+    /// Called between chunks, with no connection locked, so a paused
+    /// transfer holds up nothing else on the connection. Open file handles
+    /// and positions are kept while it waits to be resumed or cancelled.
+    fn checkpoint(&self) {
+        let mut paused = self.paused.lock().unwrap();
+        while *paused && !self.is_cancelled() {
+            paused = self.changed.wait(paused).unwrap();
+        }
+    }
+}
+
+/// What every step of a running transfer needs.
+struct TransferContext<'a> {
+    connection: &'a Mutex<SftpClient>,
+    /// The connection copied to, for copies.
+    target: Option<&'a Mutex<SftpClient>>,
+    record: &'a TransferRecord,
+    window: &'a Window,
+    control: &'a TransferControl,
+    resume: bool,
+}
+
+/// Runs a transfer to completion on the current thread, reporting progress
+/// and completion through window events. A failure is returned for the
+/// caller to retry or report; partial files are kept so a retry can resume
+/// them. With `resume`, files pick up where the destination left off when
+/// the data already there matches the source.
+pub fn execute_transfer(
+    record: &TransferRecord,
+    resume: bool,
+    window: &Window,
+    connection: &Mutex<SftpClient>,
+    target: Option<&Mutex<SftpClient>>,
+    control: &TransferControl,
+) -> Result<(), SftpError> {
+    let ctx = TransferContext {
+        connection,
+        target,
+        record,
+        window,
+        control,
+        resume,
+    };
+
+    run_transfer(&ctx)
+}
+
+/// Reports a transfer that has given up: `transfer_cancelled` if it was
+/// cancelled, `transfer_error` otherwise.
+pub fn emit_failure(window: &Window, record: &TransferRecord, error: &SftpError) {
+    match error {
+        SftpError::Cancelled { .. } => emit_cancelled(window, record),
+        _ => emit_error(window, record, error),
+    }
+}
+
+pub fn emit_retry(
+    window: &Window,
+    record: &TransferRecord,
+    attempt: u32,
+    delay: Duration,
+    error: &SftpError,
+) {
+    window.emit(
+        "transfer_retry",
+        serde_json::json!({
+            "transfer_id": record.transfer_id,
+            "type": record.direction.as_str(),
+            "attempt": attempt,
+            "max_attempts": record.options.retry.max_attempts,
+            "delay_ms": delay.as_millis() as u64,
+            "error": error
+        }),
+    ).ok();
+}
+
+pub fn emit_cancelled(window: &Window, record: &TransferRecord) {
+    window.emit(
+        "transfer_cancelled",
+        serde_json::json!({
+            "transfer_id": record.transfer_id,
+            "type": record.direction.as_str(),
+            "record": record
+        }),
+    ).ok();
+}
+
+pub fn emit_error(window: &Window, record: &TransferRecord, error: &SftpError) {
+    window.emit(
+        "transfer_error",
+        serde_json::json!({
+            "transfer_id": record.transfer_id,
+            "error": error,
+            "type": record.direction.as_str(),
+            "record": record
+        }),
+    ).ok();
+}
+
+fn lock_client(connection: &Mutex<SftpClient>) -> Result<MutexGuard<'_, SftpClient>, SftpError> {
+    connection
+        .lock()
+        .map_err(|e| SftpError::from(format!("Failed to lock client: {}", e)))
+}
+
+fn target_connection<'a>(ctx: &TransferContext<'a>) -> Result<&'a Mutex<SftpClient>, SftpError> {
+    ctx.target
+        .ok_or_else(|| SftpError::from("Copy has no target connection".to_string()))
+}
+
+/// Locks both ends of a copy, always in the same order, so two copies
+/// between the same connections in opposite directions can't each end up
+/// holding one lock while waiting for the other. Both ends being the same
+/// connection is an error rather than a deadlock.
+fn lock_pair<'a>(
+    source: &'a Mutex<SftpClient>,
+    target: &'a Mutex<SftpClient>,
+) -> Result<(MutexGuard<'a, SftpClient>, MutexGuard<'a, SftpClient>), SftpError> {
+    if std::ptr::eq(source, target) {
+        return Err(SftpError::from(
+            "Source and target must be different connections".to_string(),
+        ));
+    }
+    if std::ptr::from_ref(source) < std::ptr::from_ref(target) {
+        let source = lock_client(source)?;
+        Ok((source, lock_client(target)?))
+    } else {
+        let target = lock_client(target)?;
+        Ok((lock_client(source)?, target))
+    }
+}
+
+fn run_transfer(ctx: &TransferContext) -> Result<(), SftpError> {
+    let record = ctx.record;
+    let is_dir = match record.direction {
+        TransferDirection::Upload => fs::metadata(&record.local_path)
+            .map(|metadata| metadata.is_dir())
+            .unwrap_or(false),
+        TransferDirection::Download | TransferDirection::Copy => lock_client(ctx.connection)?
+            .sftp()
+            .stat(Path::new(&record.remote_path))
+            .map(|stat| stat.is_dir())
+            .unwrap_or(false),
+    };
+
+    if is_dir {
+        transfer_directory(ctx)
+    } else {
+        transfer_file(ctx)
+    }
+}
+
+fn transfer_file(ctx: &TransferContext) -> Result<(), SftpError> {
+    let (record, window) = (ctx.record, ctx.window);
+    let kind = record.direction.as_str();
+    let mut started = false;
+    let mut tracker = record.options.progress_tracker();
+    let mut progress = |transferred: u64, total: u64| {
+        if !started {
+            started = true;
+            emit_resumed(window, record, transferred);
+        }
+        let Some(stats) = tracker.update(transferred, total) else {
+            return;
+        };
+        window.emit(
+            &format!("{}_progress", kind),
+            serde_json::json!({
+                "connection_id": record.connection_id,
+                "path": record.remote_path,
+                "transferred": stats.transferred,
+                "total": stats.total,
+                "elapsed_ms": stats.elapsed_ms,
+                "bytes_per_second": stats.bytes_per_second,
+                "average_bytes_per_second": stats.average_bytes_per_second,
+                "eta_seconds": stats.eta_seconds,
+                "type": kind,
+                "transfer_id": record.transfer_id
+            }),
+        ).ok();
+    };
+
+    let Some((local_path, remote_path)) =
+        resolve_conflict(ctx, &record.local_path, &record.remote_path, false)?
+    else {
+        window.emit(
+            "process_finished",
+            serde_json::json!({
+                "connection_id": record.connection_id,
+                "path": record.remote_path,
+                "type": kind,
+                "transfer_id": record.transfer_id,
+                "skipped": true
+            }),
+        ).ok();
+        return Ok(());
+    };
+
+    copy_file(ctx, &local_path, &remote_path, &mut progress)?;
+    let summary = tracker.summary();
+    let verification = if record.options.verify {
+        Some(verify_file(ctx, &local_path, &remote_path)?)
+    } else {
+        None
+    };
+
+    let destination = match record.direction {
+        TransferDirection::Upload => &remote_path,
+        TransferDirection::Download | TransferDirection::Copy => &local_path,
+    };
+    window.emit(
+        "process_finished",
+        serde_json::json!({
+            "connection_id": record.connection_id,
+            "path": record.remote_path,
+            "type": kind,
+            "transfer_id": record.transfer_id,
+            "destination": destination,
+            "skipped": false,
+            "verification": verification,
+            "summary": summary
+        }),
+    ).ok();
+
+    // The verdict is reported above either way; a mismatch still fails the
+    // transfer, as it does for a file in a directory.
+    match verification {
+        Some(verification) if !verification.matched => Err(SftpError::ChecksumMismatch {
+            path: remote_path,
+            local: verification.local.digest,
+            remote: verification.remote.digest,
+        }),
+        _ => Ok(()),
+    }
+}
+
+fn verify_file(ctx: &TransferContext, local_path: &str, remote_path: &str) -> Result<Verification, SftpError> {
+    if ctx.record.direction != TransferDirection::Copy {
+        let client = lock_client(ctx.connection)?;
+        return checksum::verify(&client, local_path, remote_path);
+    }
+
+    let source = {
+        let client = lock_client(ctx.connection)?;
+        checksum::remote_checksum(&client, remote_path)?
+    };
+    let client = lock_client(target_connection(ctx)?)?;
+    let target = checksum::remote_checksum(&client, local_path)?;
+    Ok(Verification {
+        matched: source.digest == target.digest,
+        local: source,
+        remote: target,
+    })
+}
+
+fn emit_resumed(window: &Window, record: &TransferRecord, offset: u64) {
+    if offset == 0 {
+        return;
+    }
+
+    window.emit(
+        "transfer_resumed",
+        serde_json::json!({
+            "transfer_id": record.transfer_id,
+            "type": record.direction.as_str(),
+            "offset": offset
+        }),
+    ).ok();
+}
+
+/// Copies a whole tree as one job. A file that fails is reported and
+/// skipped; cancelling or losing the connection ends the job.
+fn transfer_directory(ctx: &TransferContext) -> Result<(), SftpError> {
+    let (record, window) = (ctx.record, ctx.window);
+    let kind = record.direction.as_str();
+    let mut plan = TransferPlan::default();
+    match record.direction {
+        TransferDirection::Upload => {
+            plan_upload(Path::new(&record.local_path), &record.remote_path, &mut plan)?;
+            let client = lock_client(ctx.connection)?;
+            for directory in &plan.directories {
+                ensure_remote_dir(&client, &directory.remote_path)?;
+            }
+        }
+        TransferDirection::Download => {
+            let client = lock_client(ctx.connection)?;
+            plan_remote(&client, &record.remote_path, &record.local_path, join_local, &mut plan)?;
+            for directory in &plan.directories {
+                fs::create_dir_all(&directory.local_path)
+                    .map_err(|e| SftpError::local("download", &directory.local_path, e))?;
+            }
+        }
+        TransferDirection::Copy => {
+            let client = lock_client(ctx.connection)?;
+            plan_remote(&client, &record.remote_path, &record.local_path, join_remote, &mut plan)?;
+            drop(client);
+            let target = lock_client(target_connection(ctx)?)?;
+            for directory in &plan.directories {
+                ensure_remote_dir(&target, &directory.local_path)?;
+            }
+        }
+    }
+
+    let files_total = plan.files.len();
+    let bytes_total: u64 = plan.files.iter().map(|file| file.size).sum();
+    let mut files_done = 0;
+    let mut files_skipped = 0;
+    let mut files_verified = 0;
+    let mut bytes_done = 0;
+    let mut failed = Vec::new();
+
+    let tracker = RefCell::new(record.options.progress_tracker());
+    let emit_progress = |files_done: usize, bytes_done: u64, current: &str| {
+        let Some(stats) = tracker.borrow_mut().update(bytes_done, bytes_total) else {
+            return;
+        };
+        window.emit(
+            "directory_progress",
+            serde_json::json!({
+                "connection_id": record.connection_id,
+                "path": record.remote_path,
+                "current_file": current,
+                "files_done": files_done,
+                "files_total": files_total,
+                "bytes_done": stats.transferred,
+                "bytes_total": stats.total,
+                "elapsed_ms": stats.elapsed_ms,
+                "bytes_per_second": stats.bytes_per_second,
+                "average_bytes_per_second": stats.average_bytes_per_second,
+                "eta_seconds": stats.eta_seconds,
+                "type": kind,
+                "transfer_id": record.transfer_id
+            }),
+        ).ok();
+    };
+
+    for file in &plan.files {
+        let mut progress = |transferred: u64, _total: u64| {
+            emit_progress(files_done, bytes_done + transferred, &file.remote_path);
+        };
+
+        let result = resolve_conflict(ctx, &file.local_path, &file.remote_path, true)
+            .and_then(|paths| {
+                let Some((local_path, remote_path)) = paths else {
+                    return Ok(false);
+                };
+                copy_file(ctx, &local_path, &remote_path, &mut progress)?;
+                if !record.options.verify {
+                    return Ok(true);
+                }
+                let verification = verify_file(ctx, &local_path, &remote_path)?;
+                files_verified += 1;
+                if verification.matched {
+                    Ok(true)
+                } else {
+                    Err(SftpError::ChecksumMismatch {
+                        path: remote_path,
+                        local: verification.local.digest,
+                        remote: verification.remote.digest,
+                    })
+                }
+            });
+
+        match result {
+            Ok(true) => files_done += 1,
+            Ok(false) => files_skipped += 1,
+            Err(e) if matches!(e, SftpError::Cancelled { .. }) || e.is_connection_error() => {
+                return Err(e);
+            }
+            Err(error) => failed.push(FileError {
+                local_path: file.local_path.clone(),
+                remote_path: file.remote_path.clone(),
+                error,
+            }),
+        }
+
+        bytes_done += file.size;
+        emit_progress(files_done, bytes_done, &file.remote_path);
+    }
+
+    // Deepest first, since copying into a directory changes its mtime and a
+    // read-only mode would have stopped the copy.
+    if record.options.preserve {
+        for directory in plan.directories.iter().rev() {
+            let result = preserve_attributes(ctx, &directory.local_path, &directory.remote_path);
+            if let Err(error) = result {
+                failed.push(FileError {
+                    local_path: directory.local_path.clone(),
+                    remote_path: directory.remote_path.clone(),
+                    error,
+                });
+            }
+        }
+    }
+
+    window.emit(
+        "process_finished",
+        serde_json::json!({
+            "connection_id": record.connection_id,
+            "path": record.remote_path,
+            "type": kind,
+            "transfer_id": record.transfer_id,
+            "files_done": files_done,
+            "files_total": files_total,
+            "files_skipped": files_skipped,
+            "files_verified": files_verified,
+            "failed": failed,
+            "summary": tracker.borrow().summary()
+        }),
+    ).ok();
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(SftpError::PartialFailure {
+            operation: kind.to_string(),
+            path: record.remote_path.clone(),
+            failed,
+        })
+    }
+}
+
+/// Symlinks are skipped rather than followed, so a link cycle can't make
+/// the walk recurse forever.
+fn plan_upload(local_dir: &Path, remote_dir: &str, plan: &mut TransferPlan) -> Result<(), SftpError> {
+    let local_display = local_dir.to_string_lossy().to_string();
+    plan.directories.push(PlannedDirectory {
+        local_path: local_display.clone(),
+        remote_path: remote_dir.to_string(),
+    });
+
+    let mut entries: Vec<_> = fs::read_dir(local_dir)
+        .map_err(|e| SftpError::local("upload", &local_display, e))?
+        .filter_map(Result::ok)
+        .collect();
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        let remote_path = join_remote(remote_dir, &name);
+
+        if file_type.is_dir() {
+            plan_upload(&entry.path(), &remote_path, plan)?;
+        } else if file_type.is_file() {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            plan.files.push(PlannedFile {
+                local_path: entry.path().to_string_lossy().to_string(),
+                remote_path,
+                size,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Walks a remote tree for a download or copy. `join` builds paths on the
+/// destination side, which is local for downloads and remote for copies.
+fn plan_remote(
+    client: &SftpClient,
+    remote_dir: &str,
+    destination_dir: &str,
+    join: fn(&str, &str) -> String,
+    plan: &mut TransferPlan,
+) -> Result<(), SftpError> {
+    plan.directories.push(PlannedDirectory {
+        local_path: destination_dir.to_string(),
+        remote_path: remote_dir.to_string(),
+    });
+
+    let mut entries = client
+        .sftp()
+        .readdir(Path::new(remote_dir))
+        .map_err(|e| client.remote_error("download", remote_dir, e))?;
+    entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    for (path, stat) in entries {
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        if name.is_empty() || name == "." || name == ".." {
+            continue;
+        }
+        let remote_path = join_remote(remote_dir, &name);
+        let local_path = join(destination_dir, &name);
+
+        let file_type = stat.file_type();
+        if file_type.is_dir() {
+            plan_remote(client, &remote_path, &local_path, join, plan)?;
+        } else if file_type.is_file() {
+            plan.files.push(PlannedFile {
+                local_path,
+                remote_path,
+                size: stat.size.unwrap_or(0),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies the transfer's conflict policy to one file. Returns the
+/// `(local, remote)` paths to copy between, which differ from the given
+/// ones if the file is renamed, or `None` if it is skipped. Decisions are
+/// kept on the control, so a retry doesn't ask again.
+fn resolve_conflict(
+    ctx: &TransferContext,
+    local_path: &str,
+    remote_path: &str,
+    batch: bool,
+) -> Result<Option<(String, String)>, SftpError> {
+    let record = ctx.record;
+    let policy = record.options.conflict;
+    if policy == ConflictPolicy::Overwrite {
+        return Ok(Some((local_path.to_string(), remote_path.to_string())));
+    }
+
+    let direction = record.direction;
+    let (source, destination) = match direction {
+        TransferDirection::Upload => (local_path, remote_path),
+        TransferDirection::Download | TransferDirection::Copy => (remote_path, local_path),
+    };
+    let with_destination = |target: String| match direction {
+        TransferDirection::Upload => (local_path.to_string(), target),
+        TransferDirection::Download | TransferDirection::Copy => (target, remote_path.to_string()),
+    };
+
+    if let Some(decided) = ctx.control.conflicts.lock().unwrap().get(destination) {
+        return Ok(decided.map(with_destination));
+    }
+
+    let kind = direction.as_str();
+    let (incoming, existing) = file_summaries(ctx, source, destination)?;
+    let resolution = match existing {
+        None => ConflictResolution::Overwrite,
+        Some(existing) => {
+            let decided = conflict::decide(policy, &incoming, &existing)
+                .or_else(|| ctx.control.conflicts.lock().unwrap().apply_to_all());
+            match decided {
+                Some(resolution) => resolution,
+                None => {
+                    let conflict = Conflict {
+                        transfer_id: &record.transfer_id,
+                        kind,
+                        source,
+                        destination,
+                        incoming,
+                        existing,
+                        batch,
+                    };
+                    let cancelled = || ctx.control.is_cancelled();
+                    let answer = conflict::ask(ctx.window, &conflict, &cancelled)
+                        .ok_or_else(|| SftpError::cancelled(kind, destination))?;
+                    if answer.apply_to_all {
+                        ctx.control.conflicts.lock().unwrap().set_apply_to_all(answer.resolution);
+                    }
+                    answer.resolution
+                }
+            }
+        }
+    };
+
+    let target = match resolution {
+        ConflictResolution::Overwrite => Some(destination.to_string()),
+        ConflictResolution::Skip => None,
+        ConflictResolution::Rename => Some(free_path(ctx, destination)?),
+    };
+    ctx.control.conflicts.lock().unwrap().remember(destination, target.clone());
+    Ok(target.map(with_destination))
+}
+
+/// Size and mtime of the file being copied and, if it exists, of the one
+/// it would replace.
+fn file_summaries(
+    ctx: &TransferContext,
+    source: &str,
+    destination: &str,
+) -> Result<(FileSummary, Option<FileSummary>), SftpError> {
+    let kind = ctx.record.direction.as_str();
+    let incoming = match ctx.record.direction {
+        TransferDirection::Upload => {
+            local_summary(source).map_err(|e| SftpError::local(kind, source, e))?
+        }
+        TransferDirection::Download | TransferDirection::Copy => {
+            let client = lock_client(ctx.connection)?;
+            remote_summary(&client, source).map_err(|e| client.remote_error(kind, source, e))?
+        }
+    };
+
+    let existing = match ctx.record.direction {
+        TransferDirection::Download => local_summary(destination).ok(),
+        TransferDirection::Upload | TransferDirection::Copy => {
+            let connection = ctx.target.unwrap_or(ctx.connection);
+            let client = lock_client(connection)?;
+            remote_summary(&client, destination).ok()
+        }
+    };
+    Ok((incoming, existing))
+}
+
+/// The first `name (n).ext` that doesn't exist yet on the destination side.
+fn free_path(ctx: &TransferContext, destination: &str) -> Result<String, SftpError> {
+    let connection = match ctx.record.direction {
+        TransferDirection::Copy => target_connection(ctx)?,
+        TransferDirection::Upload | TransferDirection::Download => ctx.connection,
+    };
+    let client = lock_client(connection)?;
+    let mut n = 1;
+    loop {
+        let candidate = conflict::numbered_path(destination, n);
+        let taken = match ctx.record.direction {
+            TransferDirection::Upload | TransferDirection::Copy => {
+                client.sftp().lstat(Path::new(&candidate)).is_ok()
+            }
+            TransferDirection::Download => fs::symlink_metadata(&candidate).is_ok(),
+        };
+        if !taken {
+            return Ok(candidate);
+        }
+        n += 1;
+    }
+}
+
+fn local_summary(path: &str) -> io::Result<FileSummary> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|duration| duration.as_secs());
+    Ok(FileSummary {
+        size: metadata.len(),
+        modified,
+    })
+}
+
+fn remote_summary(client: &SftpClient, path: &str) -> Result<FileSummary, ssh2::Error> {
+    let stat = client.sftp().stat(Path::new(path))?;
+    Ok(FileSummary {
+        size: stat.size.unwrap_or(0),
+        modified: stat.mtime,
+    })
+}
+
+fn preserve_attributes(ctx: &TransferContext, local_path: &str, remote_path: &str) -> Result<(), SftpError> {
+    match ctx.record.direction {
+        TransferDirection::Upload => {
+            let client = lock_client(ctx.connection)?;
+            preserve::copy_to_remote(&client, local_path, remote_path)
+        }
+        TransferDirection::Download => {
+            let client = lock_client(ctx.connection)?;
+            preserve::copy_to_local(&client, remote_path, local_path)
+        }
+        TransferDirection::Copy => preserve_between(ctx, remote_path, local_path),
+    }
+}
+
+/// Carries attributes from the source of a copy to the target. The two
+/// connections are locked one after the other, never together.
+fn preserve_between(ctx: &TransferContext, source_path: &str, target_path: &str) -> Result<(), SftpError> {
+    let attributes = {
+        let client = lock_client(ctx.connection)?;
+        preserve::remote_attributes(&client, source_path)?
+    };
+    let client = lock_client(target_connection(ctx)?)?;
+    preserve::apply_to_remote(&client, target_path, attributes)
+}
+
+fn ensure_remote_dir(client: &SftpClient, path: &str) -> Result<(), SftpError> {
+    if let Ok(stat) = client.sftp().stat(Path::new(path)) {
+        if stat.is_dir() {
+            return Ok(());
+        }
+    }
+
+    client
+        .sftp()
+        .mkdir(Path::new(path), 0o755)
+        .map_err(|e| client.remote_error("upload", path, e))
+}
+
+fn join_remote(dir: &str, name: &str) -> String {
+    format!("{}/{}", dir.trim_end_matches('/'), name)
+}
+
+fn join_local(dir: &str, name: &str) -> String {
+    Path::new(dir).join(name).to_string_lossy().to_string()
+}
+
+/// Copies one file in the transfer's direction. `progress` receives
+/// `(transferred, total)`, first with the offset the copy starts from.
+fn copy_file(
+    ctx: &TransferContext,
+    local_path: &str,
+    remote_path: &str,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<(), SftpError> {
+    match ctx.record.direction {
+        TransferDirection::Upload => write_target(ctx, ctx.connection, remote_path, |path| {
+            write_remote(ctx, local_path, path, progress)
+        }),
+        TransferDirection::Download => download_file(ctx, remote_path, local_path, progress),
+        TransferDirection::Copy => write_target(ctx, target_connection(ctx)?, local_path, |path| {
+            pipe_file(ctx, remote_path, path, progress)
+        }),
+    }
+}
+
+/// Runs `write` against the remote `path` or, for atomic transfers, against
+/// a temp file that is renamed over `path` once it is complete. The temp
+/// file is kept after a failure that may be retried, so the retry resumes
+/// it, and removed after a cancel or any other failure.
+fn write_target(
+    ctx: &TransferContext,
+    connection: &Mutex<SftpClient>,
+    path: &str,
+    write: impl FnOnce(&str) -> Result<(), SftpError>,
+) -> Result<(), SftpError> {
+    if !ctx.record.options.atomic {
+        return write(path);
+    }
+
+    let temp_path = temp_path(path, &ctx.record.transfer_id);
+    let result = write(&temp_path).and_then(|()| {
+        let client = lock_client(connection)?;
+        replace_remote(&client, &temp_path, path)
+    });
+
+    if let Err(error) = &result {
+        // Cancelled falls in neither group.
+        let retryable = error.is_connection_error() || ctx.record.options.retry.retries(error);
+        if !retryable {
+            if let Ok(client) = lock_client(connection) {
+                client.sftp().unlink(Path::new(&temp_path)).ok();
+            }
+        }
+    }
+    result
+}
+
+/// A hidden name in the target's directory, so the final rename never
+/// crosses filesystems. It only depends on the transfer, which lets a retry
+/// resume a temp file that couldn't be cleaned up.
+fn temp_path(remote_path: &str, transfer_id: &str) -> String {
+    let (dir, name) = match remote_path.rsplit_once('/') {
+        Some((dir, name)) => (Some(dir), name),
+        None => (None, remote_path),
+    };
+    let short_id: String = transfer_id.chars().take(8).collect();
+    let temp_name = format!(".{}.{}.part", name, short_id);
+
+    match dir {
+        Some(dir) => format!("{}/{}", dir, temp_name),
+        None => temp_name,
+    }
+}
+
+/// Moves a finished temp file over the target. libssh2 has no binding for
+/// `posix-rename@openssh.com`, and a plain SFTP rename refuses to replace an
+/// existing file on OpenSSH, so the server's `mv` (an atomic rename(2)) is
+/// tried next. If exec isn't allowed either, the transfer fails and the
+/// existing target is left as it was rather than replaced non-atomically.
+fn replace_remote(client: &SftpClient, temp_path: &str, remote_path: &str) -> Result<(), SftpError> {
+    let renamed = client
+        .sftp()
+        .rename(Path::new(temp_path), Path::new(remote_path), None);
+    let Err(rename_error) = renamed else {
+        return Ok(());
+    };
+
+    let command = format!("mv -f {} {}", shell_quote(temp_path), shell_quote(remote_path));
+    if client.exec(&command).is_some() {
+        return Ok(());
+    }
+
+    Err(client.remote_error("upload", remote_path, rename_error))
+}
+
+fn write_remote(
+    ctx: &TransferContext,
+    local_path: &str,
+    remote_path: &str,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<(), SftpError> {
+    let mut local_file = fs::File::open(local_path)
+        .map_err(|e| SftpError::local("upload", local_path, e))?;
+
+    let total_size = local_file
+        .metadata()
+        .map_err(|e| SftpError::local("upload", local_path, e))?
+        .len();
+
+    let (mut remote_file, offset) = {
+        let client = lock_client(ctx.connection)?;
+        let offset = if ctx.resume {
+            remote_resume_offset(&client, &mut local_file, total_size, remote_path)
+        } else {
+            0
+        };
+        (open_for_write(&client, "upload", remote_path, offset)?, offset)
+    };
+    local_file
+        .seek(SeekFrom::Start(offset))
+        .map_err(|e| SftpError::local("upload", local_path, e))?;
+
+    let mut buffer = [0u8; 8192];
+    let mut transferred = offset;
+    progress(transferred, total_size);
+
+    loop {
+        ctx.control.checkpoint();
+        if ctx.control.is_cancelled() {
+            return Err(SftpError::cancelled("upload", remote_path));
+        }
+
+        let n = local_file
+            .read(&mut buffer)
+            .map_err(|e| SftpError::local("upload", local_path, e))?;
+
+        if n == 0 {
+            break;
+        }
+        if !ctx.control.throttle(n as u64) {
+            return Err(SftpError::cancelled("upload", remote_path));
+        }
+
+        let client = lock_client(ctx.connection)?;
+        remote_file
+            .write_all(&buffer[..n])
+            .map_err(|e| client.remote_io_error("upload", remote_path, e))?;
+        drop(client);
+
+        transferred += n as u64;
+        progress(transferred, total_size);
+    }
+
+    let client = lock_client(ctx.connection)?;
+    if ctx.record.options.fsync {
+        // Servers without fsync@openssh.com just skip this.
+        remote_file.fsync().ok();
+    }
+    drop(remote_file);
+
+    if ctx.record.options.preserve {
+        preserve::copy_to_remote(&client, local_path, remote_path)?;
+    }
+
+    Ok(())
+}
+
+fn download_file(
+    ctx: &TransferContext,
+    remote_path: &str,
+    local_path: &str,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<(), SftpError> {
+    let (mut remote_file, total_size, offset) = {
+        let client = lock_client(ctx.connection)?;
+        let mut remote_file = client
+            .sftp()
+            .open(Path::new(remote_path))
+            .map_err(|e| client.remote_error("download", remote_path, e))?;
+
+        let total_size = remote_file
+            .stat()
+            .map_err(|e| client.remote_error("download", remote_path, e))?
+            .size
+            .unwrap_or(0);
+
+        let offset = if ctx.resume {
+            download_resume_offset(&mut remote_file, total_size, local_path)
+        } else {
+            0
+        };
+        remote_file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| client.remote_io_error("download", remote_path, e))?;
+        (remote_file, total_size, offset)
+    };
+
+    let mut local_file = if offset > 0 {
+        let mut local_file = OpenOptions::new()
+            .write(true)
+            .open(local_path)
+            .map_err(|e| SftpError::local("download", local_path, e))?;
+        local_file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| SftpError::local("download", local_path, e))?;
+        local_file
+    } else {
+        fs::File::create(local_path)
+            .map_err(|e| SftpError::local("download", local_path, e))?
+    };
+
+    let mut buffer = [0u8; 8192];
+    let mut transferred = offset;
+    progress(transferred, total_size);
+
+    loop {
+        ctx.control.checkpoint();
+        if ctx.control.is_cancelled() {
+            return Err(SftpError::cancelled("download", remote_path));
+        }
+
+        let n = {
+            let client = lock_client(ctx.connection)?;
+            remote_file
+                .read(&mut buffer)
+                .map_err(|e| client.remote_io_error("download", remote_path, e))?
+        };
+
+        if n == 0 {
+            break;
+        }
+        if !ctx.control.throttle(n as u64) {
+            return Err(SftpError::cancelled("download", remote_path));
+        }
+
+        local_file
+            .write_all(&buffer[..n])
+            .map_err(|e| SftpError::local("download", local_path, e))?;
+
+        transferred += n as u64;
+        progress(transferred, total_size);
+    }
+
+    local_file
+        .sync_all()
+        .map_err(|e| SftpError::local("download", local_path, e))?;
+    drop(local_file);
+
+    let client = lock_client(ctx.connection)?;
+    drop(remote_file);
+    if ctx.record.options.preserve {
+        preserve::copy_to_local(&client, remote_path, local_path)?;
+    }
+
+    Ok(())
+}
+
+/// Opens a remote file to write from `offset` on; from 0 it is created or
+/// truncated.
+fn open_for_write(
+    client: &SftpClient,
+    operation: &str,
+    path: &str,
+    offset: u64,
+) -> Result<ssh2::File, SftpError> {
+    if offset == 0 {
+        return client
+            .sftp()
+            .create(Path::new(path))
+            .map_err(|e| client.remote_error(operation, path, e));
+    }
+
+    let mut file = client
+        .sftp()
+        .open_mode(Path::new(path), OpenFlags::WRITE, 0o644, OpenType::File)
+        .map_err(|e| client.remote_error(operation, path, e))?;
+    file.seek(SeekFrom::Start(offset))
+        .map_err(|e| client.remote_io_error(operation, path, e))?;
+    Ok(file)
+}
+
+/// Streams a file from the source connection to the target. A reader
+/// thread keeps up to `PIPE_DEPTH` chunks queued while this thread writes,
+/// so neither side waits out the other's round trips and nothing touches
+/// local disk. Each side only locks its connection for one chunk at a time.
+fn pipe_file(
+    ctx: &TransferContext,
+    source_path: &str,
+    target_path: &str,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<(), SftpError> {
+    let target = target_connection(ctx)?;
+    let (mut source_file, mut target_file, offset, total_size) = {
+        let (source_client, target_client) = lock_pair(ctx.connection, target)?;
+        let mut source_file = source_client
+            .sftp()
+            .open(Path::new(source_path))
+            .map_err(|e| source_client.remote_error("copy", source_path, e))?;
+
+        let total_size = source_file
+            .stat()
+            .map_err(|e| source_client.remote_error("copy", source_path, e))?
+            .size
+            .unwrap_or(0);
+
+        let offset = if ctx.resume {
+            remote_resume_offset(&target_client, &mut source_file, total_size, target_path)
+        } else {
+            0
+        };
+
+        let target_file = open_for_write(&target_client, "copy", target_path, offset)?;
+        source_file
+            .seek(SeekFrom::Start(offset))
+            .map_err(|e| source_client.remote_io_error("copy", source_path, e))?;
+        (source_file, target_file, offset, total_size)
+    };
+
+    let (tx, rx) = mpsc::sync_channel(PIPE_DEPTH);
+    thread::scope(|scope| {
+        let source_file = &mut source_file;
+        scope.spawn(move || fill_pipe(ctx, source_file, source_path, tx));
+        drain_pipe(ctx, &mut target_file, target_path, rx, offset, total_size, progress)
+    })?;
+
+    if ctx.record.options.fsync {
+        let _client = lock_client(target)?;
+        target_file.fsync().ok();
+    }
+    drop(target_file);
+    drop(source_file);
+
+    if ctx.record.options.preserve {
+        preserve_between(ctx, source_path, target_path)?;
+    }
+
+    Ok(())
+}
+
+/// The reading half of `pipe_file`. Stops after sending an error or the
+/// empty chunk that marks the end, or once the writer has gone away.
+fn fill_pipe(
+    ctx: &TransferContext,
+    source_file: &mut ssh2::File,
+    source_path: &str,
+    tx: SyncSender<PipeChunk>,
+) {
+    loop {
+        if ctx.control.is_cancelled() {
+            return;
+        }
+
+        ctx.control.checkpoint();
+        let chunk = lock_client(ctx.connection).and_then(|client| {
+                let mut buffer = vec![0u8; PIPE_CHUNK];
+                let n = source_file
+                    .read(&mut buffer)
+                    .map_err(|e| client.remote_io_error("copy", source_path, e))?;
+                buffer.truncate(n);
+                Ok(buffer)
+            });
+
+        let last = !matches!(&chunk, Ok(buffer) if !buffer.is_empty());
+        if tx.send(chunk).is_err() || last {
+            return;
+        }
+    }
+}
+
+/// The writing half of `pipe_file`.
+fn drain_pipe(
+    ctx: &TransferContext,
+    target_file: &mut ssh2::File,
+    target_path: &str,
+    rx: Receiver<PipeChunk>,
+    offset: u64,
+    total_size: u64,
+    progress: &mut dyn FnMut(u64, u64),
+) -> Result<(), SftpError> {
+    let target = target_connection(ctx)?;
+    let mut transferred = offset;
+    progress(transferred, total_size);
+
+    // The reader only hangs up early when the transfer is cancelled.
+    for chunk in rx {
+        let chunk = chunk?;
+        if chunk.is_empty() {
+            return Ok(());
+        }
+
+        ctx.control.checkpoint();
+        if ctx.control.is_cancelled() {
+            break;
+        }
+        if !ctx.control.throttle(chunk.len() as u64) {
+            break;
+        }
+        let client = lock_client(target)?;
+
+        target_file
+            .write_all(&chunk)
+            .map_err(|e| client.remote_io_error("copy", target_path, e))?;
+
+        transferred += chunk.len() as u64;
+        progress(transferred, total_size);
+    }
+
+    Err(SftpError::cancelled("copy", target_path))
+}
+
+/// How much of `remote_path` is already a verified prefix of `source`.
+fn remote_resume_offset<S: Read + Seek>(
+    client: &SftpClient,
+    source: &mut S,
+    total_size: u64,
+    remote_path: &str,
+) -> u64 {
+    let Ok(mut remote_file) = client.sftp().open(Path::new(remote_path)) else {
+        return 0;
+    };
+    let remote_size = remote_file
+        .stat()
+        .ok()
+        .and_then(|stat| stat.size)
+        .unwrap_or(0);
+
+    resume_offset(source, total_size, &mut remote_file, remote_size)
+}
+
+/// How much of the local file is already a verified prefix of the remote one.
+fn download_resume_offset(remote_file: &mut ssh2::File, total_size: u64, local_path: &str) -> u64 {
+    let Ok(mut local_file) = fs::File::open(local_path) else {
+        return 0;
+    };
+    let local_size = local_file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+
+    resume_offset(remote_file, total_size, &mut local_file, local_size)
+}
+
+/// Where an interrupted copy of `source` into `partial` can pick up: the
+/// partial file's length, provided it isn't longer than the source and the
+/// last bytes before that point match on both sides. Anything else, including
+/// a failure to read either side, means starting over from 0.
+pub fn resume_offset<S, P>(
+    source: &mut S,
+    source_len: u64,
+    partial: &mut P,
+    partial_len: u64,
+) -> u64
+where
+    S: Read + Seek,
+    P: Read + Seek,
+{
+    if partial_len == 0 || partial_len > source_len {
+        return 0;
+    }
+
+    let len = partial_len.min(RESUME_VERIFY_LEN);
+    let start = partial_len - len;
+    match (read_range(source, start, len), read_range(partial, start, len)) {
+        (Ok(expected), Ok(actual)) if expected == actual => partial_len,
+        _ => 0,
+    }
+}
+
+fn read_range<R: Read + Seek>(reader: &mut R, start: u64, len: u64) -> io::Result<Vec<u8>> {
+    reader.seek(SeekFrom::Start(start))?;
+    let mut buffer = vec![0u8; len as usize];
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn offset(source: &[u8], partial: &[u8]) -> u64 {
+        resume_offset(
+            &mut Cursor::new(source),
+            source.len() as u64,
+            &mut Cursor::new(partial),
+            partial.len() as u64,
+        )
+    }
+
+    #[test]
+    fn resumes_from_a_matching_prefix() {
+        let source: Vec<u8> = (0..200_000u32).map(|i| i as u8).collect();
+        assert_eq!(offset(&source, &source[..150_000]), 150_000);
+        assert_eq!(offset(&source, &source), source.len() as u64);
+    }
+
+    #[test]
+    fn restarts_from_a_zero_length_partial() {
+        assert_eq!(offset(b"some data", b""), 0);
+        assert_eq!(offset(b"", b""), 0);
+    }
+
+    #[test]
+    fn restarts_when_the_partial_is_longer_than_the_source() {
+        assert_eq!(offset(b"short", b"short and then some"), 0);
+    }
+
+    #[test]
+    fn restarts_when_the_tail_differs() {
+        let source = vec![7u8; 100_000];
+        let mut partial = source[..80_000].to_vec();
+        *partial.last_mut().unwrap() = 8;
+        assert_eq!(offset(&source, &partial), 0);
+    }
+
+    #[test]
+    fn temp_path_stays_next_to_the_target() {
+        assert_eq!(
+            temp_path("/data/report.csv", "0123456789abcdef"),
+            "/data/.report.csv.01234567.part"
+        );
+        assert_eq!(temp_path("report.csv", "abc"), ".report.csv.abc.part");
+        assert_eq!(temp_path("/d/f", "ééééééééé"), "/d/.f.éééééééé.part");
+    }
+
+    #[test]
+    fn only_checks_the_last_verify_window() {
+        let source = vec![7u8; 200_000];
+        let mut partial = source[..150_000].to_vec();
+        partial[0] = 8;
+        assert_eq!(offset(&source, &partial), 150_000);
+    }
+}
@@ -0,0 +1,160 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use ssh2::{Channel, Session};
+
+/// The pump sleeps when neither side has anything to move, starting short so
+/// interactive traffic stays snappy and doubling up to the cap while idle.
+const IDLE_SLEEP_MIN: Duration = Duration::from_millis(1);
+const IDLE_SLEEP_MAX: Duration = Duration::from_millis(50);
+
+/// A `direct-tcpip` channel through a jump host, exposed as a local socket so
+/// the next hop's `Session` can run over it like a normal TCP connection.
+pub struct JumpTunnel {
+    stop: Arc<AtomicBool>,
+    pump: Option<JoinHandle<()>>,
+}
+
+impl JumpTunnel {
+    /// Opens a channel from `session` to `host:port` and returns the tunnel
+    /// together with the local end of it.
+    pub fn open(session: Session, host: &str, port: u16) -> Result<(Self, TcpStream), String> {
+        let channel = session
+            .channel_direct_tcpip(host, port, None)
+            .map_err(|e| format!("Failed to open tunnel to {}:{}: {}", host, port, e))?;
+
+        let (local, inner) = socket_pair()?;
+        inner
+            .set_nonblocking(true)
+            .map_err(|e| format!("Failed to configure tunnel socket: {}", e))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop_clone = stop.clone();
+        let pump = thread::spawn(move || pump(session, channel, inner, &stop_clone));
+
+        Ok((
+            Self {
+                stop,
+                pump: Some(pump),
+            },
+            local,
+        ))
+    }
+}
+
+impl Drop for JumpTunnel {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(pump) = self.pump.take() {
+            pump.join().ok();
+        }
+    }
+}
+
+fn socket_pair() -> Result<(TcpStream, TcpStream), String> {
+    let listener = TcpListener::bind("127.0.0.1:0")
+        .map_err(|e| format!("Failed to bind tunnel socket: {}", e))?;
+    let addr = listener
+        .local_addr()
+        .map_err(|e| format!("Failed to bind tunnel socket: {}", e))?;
+
+    let local = TcpStream::connect(addr)
+        .map_err(|e| format!("Failed to connect tunnel socket: {}", e))?;
+    let local_addr = local
+        .local_addr()
+        .map_err(|e| format!("Failed to connect tunnel socket: {}", e))?;
+
+    // Only accept our own connection, not whoever else raced to the port.
+    loop {
+        let (inner, peer) = listener
+            .accept()
+            .map_err(|e| format!("Failed to accept tunnel socket: {}", e))?;
+        if peer == local_addr {
+            return Ok((local, inner));
+        }
+    }
+}
+
+/// Shuttles bytes between the local socket and the SSH channel until either
+/// side closes or the tunnel is dropped. The session runs non-blocking so a
+/// single thread can service both directions.
+fn pump(session: Session, mut channel: Channel, mut socket: TcpStream, stop: &AtomicBool) {
+    session.set_blocking(false);
+
+    let mut outbound = Vec::new();
+    let mut inbound = Vec::new();
+    let mut buffer = [0u8; 32 * 1024];
+    let mut idle_sleep = IDLE_SLEEP_MIN;
+
+    while !stop.load(Ordering::Relaxed) {
+        let mut progressed = false;
+
+        if outbound.is_empty() {
+            match socket.read(&mut buffer) {
+                // The next hop's session went away; nobody needs the tunnel.
+                Ok(0) => break,
+                Ok(n) => {
+                    outbound.extend_from_slice(&buffer[..n]);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if !outbound.is_empty() {
+            match channel.write(&outbound) {
+                Ok(n) => {
+                    outbound.drain(..n);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if inbound.is_empty() {
+            match channel.read(&mut buffer) {
+                Ok(0) if channel.eof() => break,
+                Ok(0) => {}
+                Ok(n) => {
+                    inbound.extend_from_slice(&buffer[..n]);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        if !inbound.is_empty() {
+            match socket.write(&inbound) {
+                Ok(n) => {
+                    inbound.drain(..n);
+                    progressed = true;
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                Err(_) => break,
+            }
+        }
+
+        // Keeps the jump host's session alive; sends only when one is due.
+        if let Err(e) = session.keepalive_send() {
+            if std::io::Error::from(e).kind() != ErrorKind::WouldBlock {
+                break;
+            }
+        }
+
+        if progressed {
+            idle_sleep = IDLE_SLEEP_MIN;
+        } else {
+            thread::sleep(idle_sleep);
+            idle_sleep = (idle_sleep * 2).min(IDLE_SLEEP_MAX);
+        }
+    }
+
+    channel.close().ok();
+}
@@ -1,13 +1,231 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// A password or passphrase. The contents are wiped from memory on drop,
+/// hidden from `Debug`, and deliberately not `Serialize`, so a secret can
+/// come in over IPC but never goes back out.
+#[derive(Deserialize, Clone)]
+#[serde(from = "String")]
+pub struct Secret(Zeroizing<String>);
+
+impl Secret {
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for Secret {
+    fn from(value: String) -> Self {
+        Secret(Zeroizing::new(value))
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret(<redacted>)")
+    }
+}
 
-#[derive(Serialize, Deserialize, Clone)]
+const DEFAULT_KEEPALIVE_INTERVAL: u32 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
 pub struct ConnectionConfig {
     pub host: String,
     pub port: u16,
     pub username: String,
     pub private_key_path: Option<String>,
-    pub passphrase: Option<String>,
-    pub password: Option<String>
+    #[serde(default, skip_serializing)]
+    pub passphrase: Option<Secret>,
+    #[serde(default, skip_serializing)]
+    pub password: Option<Secret>,
+    #[serde(default)]
+    pub known_hosts_path: Option<String>,
+    #[serde(default)]
+    pub use_agent: bool,
+    #[serde(default)]
+    pub auth_methods: Option<Vec<AuthMethod>>,
+    /// Bastions to tunnel through, outermost first. Each hop authenticates
+    /// and checks its host key with its own settings; nested `jump_hosts`
+    /// on a hop are ignored.
+    #[serde(default)]
+    pub jump_hosts: Vec<ConnectionConfig>,
+    /// Seconds between SSH keepalives; 30 when unset, 0 disables them.
+    #[serde(default)]
+    pub keepalive_interval: Option<u32>,
+    /// Re-establish the session automatically when it drops. The password
+    /// and passphrase then stay in memory for the life of the connection.
+    #[serde(default)]
+    pub auto_reconnect: bool,
+    #[serde(default)]
+    pub timeouts: Timeouts,
+}
+
+/// Limits in seconds for each stage of a connection; 0 waits indefinitely.
+/// `operation` applies to every individual SFTP request once connected.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct Timeouts {
+    pub connect: u32,
+    pub handshake: u32,
+    pub auth: u32,
+    pub operation: u32,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: 15,
+            handshake: 15,
+            auth: 30,
+            operation: 30,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// A copy with passwords and passphrases removed, jump hosts included.
+    pub fn without_secrets(&self) -> ConnectionConfig {
+        ConnectionConfig {
+            password: None,
+            passphrase: None,
+            jump_hosts: self.jump_hosts.iter().map(Self::without_secrets).collect(),
+            ..self.clone()
+        }
+    }
+
+    pub fn keepalive_interval(&self) -> u32 {
+        self.keepalive_interval.unwrap_or(DEFAULT_KEEPALIVE_INTERVAL)
+    }
+
+    /// The authentication methods to try, in order. Without an explicit list
+    /// this is whatever credentials were supplied, then keyboard-interactive.
+    pub fn auth_chain(&self) -> Vec<AuthMethod> {
+        if let Some(methods) = &self.auth_methods {
+            return methods.clone();
+        }
+
+        let mut chain = Vec::new();
+        if self.use_agent {
+            chain.push(AuthMethod::Agent);
+        }
+        if self.private_key_path.is_some() {
+            chain.push(AuthMethod::PublicKey);
+        }
+        if self.password.is_some() {
+            chain.push(AuthMethod::Password);
+        }
+        chain.push(AuthMethod::KeyboardInteractive);
+        chain
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    Agent,
+    PublicKey,
+    Password,
+    KeyboardInteractive,
+}
+
+impl AuthMethod {
+    /// The method name used on the wire, as listed by `Session::auth_methods`.
+    pub fn protocol_name(self) -> &'static str {
+        match self {
+            AuthMethod::Agent | AuthMethod::PublicKey => "publickey",
+            AuthMethod::Password => "password",
+            AuthMethod::KeyboardInteractive => "keyboard-interactive",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthOutcome {
+    Success,
+    Partial,
+    Failed,
+    Skipped,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AuthAttempt {
+    pub method: AuthMethod,
+    pub identity: Option<String>,
+    pub outcome: AuthOutcome,
+    pub error: Option<String>,
+}
+
+impl AuthAttempt {
+    pub fn skipped(method: AuthMethod, reason: &str) -> Self {
+        Self {
+            method,
+            identity: None,
+            outcome: AuthOutcome::Skipped,
+            error: Some(reason.to_string()),
+        }
+    }
+
+    pub fn failed(method: AuthMethod, identity: Option<String>, error: String) -> Self {
+        Self {
+            method,
+            identity,
+            outcome: AuthOutcome::Failed,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct AuthReport {
+    pub host: String,
+    pub server_methods: Vec<String>,
+    pub attempts: Vec<AuthAttempt>,
+    /// The server stopped answering; the remaining methods weren't tried.
+    #[serde(skip)]
+    pub timed_out: bool,
+}
+
+impl AuthReport {
+    /// The identity of the attempt that completed authentication, if any.
+    pub fn identity(&self) -> Option<&str> {
+        self.attempts
+            .iter()
+            .rev()
+            .find(|attempt| attempt.outcome == AuthOutcome::Success)
+            .and_then(|attempt| attempt.identity.as_deref())
+    }
+
+    pub fn summary(&self) -> String {
+        if self.attempts.is_empty() {
+            return "No authentication method provided".to_string();
+        }
+
+        self.attempts
+            .iter()
+            .map(|attempt| {
+                let mut line = format!("{:?}", attempt.method);
+                if let Some(identity) = &attempt.identity {
+                    line.push_str(&format!(" ({})", identity));
+                }
+                match &attempt.error {
+                    Some(error) => line.push_str(&format!(": {}", error)),
+                    None => line.push_str(&format!(": {:?}", attempt.outcome)),
+                }
+                line
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct ConnectionInfo {
+    pub connection_id: String,
+    pub identity: Option<String>,
+    pub auth: AuthReport,
 }
 
 #[derive(Serialize, Clone)]
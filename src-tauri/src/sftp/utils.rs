@@ -1,3 +1,11 @@
+use base64::engine::general_purpose::STANDARD_NO_PAD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use ssh2::ErrorCode;
+
+// LIBSSH2_ERROR_TIMEOUT, returned once a call exceeds `Session::set_timeout`.
+const LIBSSH2_ERROR_TIMEOUT: i32 = -9;
+
 pub fn format_permissions(mode: u32) -> String {
     let mut perms = String::new();
 
@@ -24,4 +32,58 @@ pub fn format_permissions(mode: u32) -> String {
     perms.push(if mode & 0o001 != 0 { 'x' } else { '-' });
 
     perms
-}
\ No newline at end of file
+}
+
+/// OpenSSH-style `SHA256:...` fingerprint of a public key blob.
+pub fn format_fingerprint(key: &[u8]) -> String {
+    format!("SHA256:{}", STANDARD_NO_PAD.encode(Sha256::digest(key)))
+}
+
+/// Glob match supporting `*` and `?`, as used by OpenSSH host patterns.
+pub fn wildcard_match(pattern: &str, text: &str) -> bool {
+    fn matches(pattern: &[u8], text: &[u8]) -> bool {
+        match (pattern.first(), text.first()) {
+            (None, None) => true,
+            (Some(b'*'), _) => {
+                matches(&pattern[1..], text) || (!text.is_empty() && matches(pattern, &text[1..]))
+            }
+            (Some(b'?'), Some(_)) => matches(&pattern[1..], &text[1..]),
+            (Some(p), Some(t)) if p == t => matches(&pattern[1..], &text[1..]),
+            _ => false,
+        }
+    }
+    matches(pattern.as_bytes(), text.as_bytes())
+}
+
+/// Matches `name` against a list of OpenSSH patterns, case-insensitively. A
+/// matching negated (`!`) pattern overrides any positive match.
+pub fn match_pattern_list<'a>(patterns: impl IntoIterator<Item = &'a str>, name: &str) -> bool {
+    let name = name.to_lowercase();
+    let mut matched = false;
+    for pattern in patterns {
+        let pattern = pattern.to_lowercase();
+        if let Some(negated) = pattern.strip_prefix('!') {
+            if wildcard_match(negated, &name) {
+                return false;
+            }
+        } else if wildcard_match(&pattern, &name) {
+            matched = true;
+        }
+    }
+    matched
+}
+
+pub fn is_timeout(error: &ssh2::Error) -> bool {
+    error.code() == ErrorCode::Session(LIBSSH2_ERROR_TIMEOUT)
+}
+
+/// Single-quotes `value` for a POSIX shell. A leading `-` is kept from
+/// being read as an option by making the path explicitly relative.
+pub fn shell_quote(value: &str) -> String {
+    let value = if value.starts_with('-') {
+        format!("./{}", value)
+    } else {
+        value.to_string()
+    };
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
@@ -1,11 +1,39 @@
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Window};
 
-use crate::sftp::SftpClient;
+use crate::sftp::prompt::WindowPrompter;
+use crate::sftp::{SftpClient, SftpError};
+
+const KEEPALIVE_POLL: Duration = Duration::from_secs(5);
+const RECONNECT_ATTEMPTS: u32 = 3;
+const RECONNECT_DELAY: Duration = Duration::from_secs(2);
+
+/// What a connection was opened to, kept for the transfer history.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ConnectionDetails {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub profile_id: Option<String>,
+}
+
+struct PooledConnection {
+    client: Arc<Mutex<SftpClient>>,
+    window: Window,
+    details: ConnectionDetails,
+}
+
+type ConnectionMap = Arc<Mutex<HashMap<String, PooledConnection>>>;
 
 pub struct ConnectionPool {
-    connections: Mutex<HashMap<String, Arc<Mutex<SftpClient>>>>,
+    connections: ConnectionMap,
+    keepalive_started: AtomicBool,
 }
 
 impl Default for ConnectionPool {
@@ -17,23 +45,206 @@ impl Default for ConnectionPool {
 impl ConnectionPool {
     pub fn new() -> Self {
         Self {
-            connections: Mutex::new(HashMap::new()),
+            connections: Arc::new(Mutex::new(HashMap::new())),
+            keepalive_started: AtomicBool::new(false),
         }
     }
 
-    pub fn add(&self, id: String, client: SftpClient) -> Arc<Mutex<SftpClient>> {
+    pub fn add(
+        &self,
+        id: String,
+        client: SftpClient,
+        details: ConnectionDetails,
+        window: Window,
+    ) -> Arc<Mutex<SftpClient>> {
         let client = Arc::new(Mutex::new(client));
-        self.connections.lock().unwrap().insert(id.clone(), client.clone());
+        emit_state(&window, &id, "connected", None);
+        self.connections.lock().unwrap().insert(
+            id,
+            PooledConnection {
+                client: client.clone(),
+                window,
+                details,
+            },
+        );
+        self.start_keepalive();
         client
     }
 
     pub fn get(&self, id: &str) -> Option<Arc<Mutex<SftpClient>>> {
-        self.connections.lock().unwrap().get(id).cloned()
+        self.connections
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|connection| connection.client.clone())
+    }
+
+    pub fn details(&self, id: &str) -> Option<ConnectionDetails> {
+        self.connections
+            .lock()
+            .unwrap()
+            .get(id)
+            .map(|connection| connection.details.clone())
     }
 
     pub fn remove(&self, id: &str) -> Option<Arc<Mutex<SftpClient>>> {
-        self.connections.lock().unwrap().remove(id)
+        self.connections
+            .lock()
+            .unwrap()
+            .remove(id)
+            .map(|connection| connection.client)
+    }
+
+    /// Runs `op` on the connection. If it fails because the session died,
+    /// the connection is re-established (when the config allows it) and `op`
+    /// is retried once; otherwise the connection is dropped from the pool.
+    pub fn run<T>(
+        &self,
+        id: &str,
+        op: impl Fn(&SftpClient) -> Result<T, SftpError>,
+    ) -> Result<T, SftpError> {
+        let (client_arc, window) = self.entry(id)?;
+        let mut client = client_arc
+            .lock()
+            .map_err(|e| SftpError::from(format!("Failed to lock client: {}", e)))?;
+
+        match op(&client) {
+            Ok(value) => Ok(value),
+            Err(e) if !e.is_connection_error() || client.is_alive() => Err(e),
+            Err(e) => {
+                if recover(&mut client, id, &window).is_err() {
+                    drop(client);
+                    self.remove(id);
+                    return Err(e);
+                }
+                op(&client)
+            }
+        }
+    }
+
+    /// Returns the connection once it is known to work, reconnecting it
+    /// first if it has died. A connection that can't be recovered is
+    /// dropped from the pool.
+    pub fn ensure_alive(&self, id: &str) -> Result<Arc<Mutex<SftpClient>>, SftpError> {
+        let (client_arc, window) = self.entry(id)?;
+        let mut client = client_arc
+            .lock()
+            .map_err(|e| SftpError::from(format!("Failed to lock client: {}", e)))?;
+
+        if !client.is_alive() {
+            if let Err(e) = recover(&mut client, id, &window) {
+                drop(client);
+                self.remove(id);
+                return Err(e);
+            }
+        }
+
+        drop(client);
+        Ok(client_arc)
+    }
+
+    fn entry(&self, id: &str) -> Result<(Arc<Mutex<SftpClient>>, Window), SftpError> {
+        let connections = self.connections.lock().unwrap();
+        let connection = connections
+            .get(id)
+            .ok_or_else(|| SftpError::ConnectionNotFound {
+                connection_id: id.to_string(),
+            })?;
+        Ok((connection.client.clone(), connection.window.clone()))
     }
+
+    /// Starts the background thread that sends keepalives on idle
+    /// connections and recovers the ones that turn out to be dead. Busy
+    /// connections are skipped; their own traffic keeps them alive.
+    fn start_keepalive(&self) {
+        if self.keepalive_started.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let connections = Arc::downgrade(&self.connections);
+        thread::spawn(move || loop {
+            thread::sleep(KEEPALIVE_POLL);
+            let Some(connections) = connections.upgrade() else {
+                return;
+            };
+
+            let snapshot: Vec<_> = connections
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(id, connection)| {
+                    (id.clone(), connection.client.clone(), connection.window.clone())
+                })
+                .collect();
+
+            for (id, client_arc, window) in snapshot {
+                let Ok(mut client) = client_arc.try_lock() else {
+                    continue;
+                };
+                if client.keepalive().is_ok() {
+                    continue;
+                }
+
+                if recover(&mut client, &id, &window).is_err() {
+                    drop(client);
+                    connections.lock().unwrap().remove(&id);
+                }
+            }
+        });
+    }
+}
+
+/// Reconnects a dead client in place, emitting `connection_state` events as
+/// it goes. Host key and authentication failures are not retried.
+fn recover(client: &mut SftpClient, id: &str, window: &Window) -> Result<(), SftpError> {
+    if !client.auto_reconnect() {
+        emit_state(window, id, "lost", None);
+        return Err(SftpError::ConnectionLost {
+            operation: "reconnect".to_string(),
+            message: "Automatic reconnection is disabled".to_string(),
+        });
+    }
+
+    emit_state(window, id, "reconnecting", None);
+
+    let host = client.host().to_string();
+    let mut attempt = 1;
+    let error = loop {
+        let mut prompter = WindowPrompter::new(window.clone(), &host);
+        match client.reconnect(Some(&mut prompter)) {
+            Ok(()) => {
+                emit_state(window, id, "connected", None);
+                return Ok(());
+            }
+            Err(e) if is_transient(&e) && attempt < RECONNECT_ATTEMPTS => {
+                thread::sleep(RECONNECT_DELAY * attempt);
+                attempt += 1;
+            }
+            Err(e) => break e,
+        }
+    };
+
+    emit_state(window, id, "lost", Some(&error.to_string()));
+    Err(error)
+}
+
+/// Failures worth another reconnect attempt, unlike a changed host key or
+/// rejected credentials.
+fn is_transient(error: &SftpError) -> bool {
+    error.is_connection_error() || matches!(error, SftpError::Other { .. })
+}
+
+fn emit_state(window: &Window, id: &str, state: &str, error: Option<&str>) {
+    window
+        .emit(
+            "connection_state",
+            serde_json::json!({
+                "connection_id": id,
+                "state": state,
+                "error": error
+            }),
+        )
+        .ok();
 }
 
-pub static CONNECTION_POOL: Lazy<ConnectionPool> = Lazy::new(ConnectionPool::new);
\ No newline at end of file
+pub static CONNECTION_POOL: Lazy<ConnectionPool> = Lazy::new(ConnectionPool::new);
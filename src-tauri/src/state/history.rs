@@ -0,0 +1,388 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::sftp::transfer::{TransferDirection, TransferRecord};
+use crate::sftp::SftpError;
+use crate::state::connection_pool::ConnectionDetails;
+
+const HISTORY_FILE: &str = "transfer_history.jsonl";
+
+const CSV_HEADER: &str = "id,started_at_ms,finished_at_ms,duration_ms,direction,connection_id,target_connection_id,host,username,profile_id,local_path,remote_path,bytes_transferred,outcome,error";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferOutcome {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl TransferOutcome {
+    fn as_str(self) -> &'static str {
+        match self {
+            TransferOutcome::Completed => "completed",
+            TransferOutcome::Failed => "failed",
+            TransferOutcome::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub record: TransferRecord,
+    /// Where the connection went; connection IDs don't outlive the session.
+    pub connection: Option<ConnectionDetails>,
+    pub started_at_ms: u64,
+    pub finished_at_ms: u64,
+    pub duration_ms: u64,
+    pub bytes_transferred: u64,
+    pub outcome: TransferOutcome,
+    pub error: Option<String>,
+}
+
+impl HistoryEntry {
+    pub fn new(
+        record: &TransferRecord,
+        connection: Option<ConnectionDetails>,
+        started_at: SystemTime,
+        bytes_transferred: u64,
+        result: &Result<(), SftpError>,
+    ) -> Self {
+        let started_at_ms = unix_millis(started_at);
+        let finished_at_ms = unix_millis(SystemTime::now());
+        let (outcome, error) = match result {
+            Ok(()) => (TransferOutcome::Completed, None),
+            Err(e @ SftpError::Cancelled { .. }) => {
+                (TransferOutcome::Cancelled, Some(e.to_string()))
+            }
+            Err(e) => (TransferOutcome::Failed, Some(e.to_string())),
+        };
+
+        Self {
+            id: Uuid::new_v4().to_string(),
+            record: record.clone(),
+            connection,
+            started_at_ms,
+            finished_at_ms,
+            duration_ms: finished_at_ms.saturating_sub(started_at_ms),
+            bytes_transferred,
+            outcome,
+            error,
+        }
+    }
+}
+
+/// Filters for listing history. Every field is optional; entries come back
+/// newest first.
+#[derive(Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct HistoryQuery {
+    pub direction: Option<TransferDirection>,
+    pub outcome: Option<TransferOutcome>,
+    pub connection_id: Option<String>,
+    pub host: Option<String>,
+    pub profile_id: Option<String>,
+    /// Case-insensitive substring of the local or remote path.
+    pub search: Option<String>,
+    pub since_ms: Option<u64>,
+    pub until_ms: Option<u64>,
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let connection = entry.connection.as_ref();
+        let search = self.search.as_ref().map(|search| search.to_lowercase());
+
+        self.direction.is_none_or(|direction| entry.record.direction == direction)
+            && self.outcome.is_none_or(|outcome| entry.outcome == outcome)
+            && self
+                .connection_id
+                .as_ref()
+                .is_none_or(|id| &entry.record.connection_id == id)
+            && self
+                .host
+                .as_ref()
+                .is_none_or(|host| connection.is_some_and(|c| c.host.eq_ignore_ascii_case(host)))
+            && self
+                .profile_id
+                .as_ref()
+                .is_none_or(|id| connection.is_some_and(|c| c.profile_id.as_ref() == Some(id)))
+            && search.is_none_or(|search| {
+                entry.record.local_path.to_lowercase().contains(&search)
+                    || entry.record.remote_path.to_lowercase().contains(&search)
+            })
+            && self.since_ms.is_none_or(|since| entry.started_at_ms >= since)
+            && self.until_ms.is_none_or(|until| entry.started_at_ms <= until)
+    }
+}
+
+#[derive(Serialize)]
+pub struct HistoryPage {
+    pub entries: Vec<HistoryEntry>,
+    /// Matching entries before paging.
+    pub total: usize,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+/// Every finished transfer, appended as one JSON line to a file in the
+/// app's config directory.
+pub struct TransferHistory {
+    path: Option<PathBuf>,
+    lock: Mutex<()>,
+}
+
+impl TransferHistory {
+    pub fn new() -> Self {
+        Self {
+            path: crate::state::app_config_dir().map(|dir| dir.join(HISTORY_FILE)),
+            lock: Mutex::new(()),
+        }
+    }
+
+    pub fn record(&self, entry: &HistoryEntry) -> Result<(), SftpError> {
+        let path = self.path()?;
+        let mut line = serde_json::to_string(entry).map_err(|e| SftpError::Other {
+            message: format!("Failed to serialize history entry: {}", e),
+        })?;
+        line.push('\n');
+
+        let _guard = self.lock.lock().unwrap();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| SftpError::local("record history", &parent.to_string_lossy(), e))?;
+        }
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| file.write_all(line.as_bytes()))
+            .map_err(|e| SftpError::local("record history", &path.to_string_lossy(), e))
+    }
+
+    pub fn query(&self, query: &HistoryQuery) -> Result<HistoryPage, SftpError> {
+        let matching = self.matching(query)?;
+        let total = matching.len();
+        let entries = matching
+            .into_iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .collect();
+        Ok(HistoryPage { entries, total })
+    }
+
+    pub fn get(&self, id: &str) -> Result<HistoryEntry, SftpError> {
+        self.load()?
+            .into_iter()
+            .find(|entry| entry.id == id)
+            .ok_or_else(|| SftpError::HistoryEntryNotFound {
+                entry_id: id.to_string(),
+            })
+    }
+
+    /// Writes every entry matching the query's filters to `path`, ignoring
+    /// its paging, and returns how many were written.
+    pub fn export(
+        &self,
+        query: &HistoryQuery,
+        format: ExportFormat,
+        path: &Path,
+    ) -> Result<usize, SftpError> {
+        let entries = self.matching(query)?;
+        let contents = match format {
+            ExportFormat::Json => {
+                serde_json::to_string_pretty(&entries).map_err(|e| SftpError::Other {
+                    message: format!("Failed to serialize history: {}", e),
+                })?
+            }
+            ExportFormat::Csv => {
+                let mut csv = String::from(CSV_HEADER);
+                csv.push('\n');
+                for entry in &entries {
+                    csv.push_str(&csv_row(entry));
+                    csv.push('\n');
+                }
+                csv
+            }
+        };
+
+        fs::write(path, contents)
+            .map_err(|e| SftpError::local("export history", &path.to_string_lossy(), e))?;
+        Ok(entries.len())
+    }
+
+    pub fn clear(&self) -> Result<(), SftpError> {
+        let path = self.path()?;
+        let _guard = self.lock.lock().unwrap();
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(SftpError::local("clear history", &path.to_string_lossy(), e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn matching(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>, SftpError> {
+        let mut entries: Vec<_> = self
+            .load()?
+            .into_iter()
+            .filter(|entry| query.matches(entry))
+            .collect();
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// Lines that don't parse, e.g. one cut short by a crash, are skipped.
+    fn load(&self) -> Result<Vec<HistoryEntry>, SftpError> {
+        let path = self.path()?;
+        let _guard = self.lock.lock().unwrap();
+        match fs::read_to_string(path) {
+            Ok(contents) => Ok(contents
+                .lines()
+                .filter_map(|line| serde_json::from_str(line).ok())
+                .collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(SftpError::local("read history", &path.to_string_lossy(), e)),
+        }
+    }
+
+    fn path(&self) -> Result<&PathBuf, SftpError> {
+        self.path.as_ref().ok_or_else(|| SftpError::Other {
+            message: "Could not determine config directory".to_string(),
+        })
+    }
+}
+
+impl Default for TransferHistory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn csv_row(entry: &HistoryEntry) -> String {
+    let connection = entry.connection.as_ref();
+    let fields = [
+        entry.id.clone(),
+        entry.started_at_ms.to_string(),
+        entry.finished_at_ms.to_string(),
+        entry.duration_ms.to_string(),
+        entry.record.direction.as_str().to_string(),
+        entry.record.connection_id.clone(),
+        entry.record.target_connection_id.clone().unwrap_or_default(),
+        connection.map(|c| c.host.clone()).unwrap_or_default(),
+        connection.map(|c| c.username.clone()).unwrap_or_default(),
+        connection.and_then(|c| c.profile_id.clone()).unwrap_or_default(),
+        entry.record.local_path.clone(),
+        entry.record.remote_path.clone(),
+        entry.bytes_transferred.to_string(),
+        entry.outcome.as_str().to_string(),
+        entry.error.clone().unwrap_or_default(),
+    ];
+    fields.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(",")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+pub static TRANSFER_HISTORY: Lazy<TransferHistory> = Lazy::new(TransferHistory::new);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sftp::transfer::TransferOptions;
+
+    fn history(name: &str) -> TransferHistory {
+        let path = std::env::temp_dir()
+            .join(format!("christoph-history-{}-{}.jsonl", name, std::process::id()));
+        fs::remove_file(&path).ok();
+        TransferHistory {
+            path: Some(path),
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn entry(
+        direction: TransferDirection,
+        local_path: &str,
+        result: Result<(), SftpError>,
+    ) -> HistoryEntry {
+        let record = TransferRecord::new(
+            "connection".to_string(),
+            direction,
+            local_path.to_string(),
+            "/remote/file".to_string(),
+            TransferOptions::default(),
+        );
+        HistoryEntry::new(&record, None, SystemTime::now(), 42, &result)
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field("cr\r"), "\"cr\r\"");
+    }
+
+    #[test]
+    fn csv_rows_match_the_header() {
+        let row = csv_row(&entry(TransferDirection::Upload, "/local/file", Ok(())));
+        assert_eq!(row.split(',').count(), CSV_HEADER.split(',').count());
+    }
+
+    #[test]
+    fn records_queries_newest_first_and_clears() {
+        let history = history("round-trip");
+        let cancelled = Err(SftpError::cancelled("download", "/remote/file"));
+        history.record(&entry(TransferDirection::Upload, "/local/a", Ok(()))).unwrap();
+        history.record(&entry(TransferDirection::Download, "/local/b", cancelled)).unwrap();
+
+        let page = history.query(&HistoryQuery::default()).unwrap();
+        assert_eq!(page.total, 2);
+        assert_eq!(page.entries[0].record.local_path, "/local/b");
+        assert_eq!(page.entries[0].outcome, TransferOutcome::Cancelled);
+
+        let uploads = HistoryQuery {
+            direction: Some(TransferDirection::Upload),
+            ..HistoryQuery::default()
+        };
+        let page = history.query(&uploads).unwrap();
+        assert_eq!(page.total, 1);
+        assert!(history.get(&page.entries[0].id).is_ok());
+
+        history.clear().unwrap();
+        assert_eq!(history.query(&HistoryQuery::default()).unwrap().total, 0);
+        assert!(matches!(
+            history.get("missing"),
+            Err(SftpError::HistoryEntryNotFound { .. })
+        ));
+    }
+}
@@ -1,2 +1,28 @@
 pub mod connection_pool;
+pub mod history;
+pub mod profiles;
+pub mod transfer_queue;
+pub mod vault;
 
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const APP_DIR_NAME: &str = "christoph";
+
+/// Where the app keeps its own files (profiles etc.).
+pub fn app_config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(APP_DIR_NAME))
+}
+
+/// Writes to a sibling temp file and renames it into place, so a crash
+/// never leaves a half-written file behind.
+pub fn write_file_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
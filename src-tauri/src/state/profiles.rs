@@ -0,0 +1,193 @@
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::sftp::ConnectionConfig;
+
+const PROFILES_FILE: &str = "profiles.json";
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ConnectionProfile {
+    pub id: String,
+    pub name: String,
+    pub config: ConnectionConfig,
+    /// Vault entry holding the password, used when none is given at connect time.
+    #[serde(default)]
+    pub password_secret_id: Option<String>,
+    /// Vault entry holding the key passphrase.
+    #[serde(default)]
+    pub passphrase_secret_id: Option<String>,
+    /// Vault entries for each jump host, in the order of `config.jump_hosts`.
+    #[serde(default)]
+    pub jump_host_secrets: Vec<JumpHostSecrets>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct JumpHostSecrets {
+    #[serde(default)]
+    pub password_secret_id: Option<String>,
+    #[serde(default)]
+    pub passphrase_secret_id: Option<String>,
+}
+
+/// Saved connection profiles, persisted as JSON in the app's config
+/// directory. Passwords and passphrases are stripped before anything is
+/// written; they are either supplied when connecting or looked up in the
+/// vault through the profile's secret IDs.
+pub struct ProfileStore {
+    path: Option<PathBuf>,
+    profiles: Mutex<Option<Vec<ConnectionProfile>>>,
+}
+
+impl ProfileStore {
+    pub fn new() -> Self {
+        Self {
+            path: crate::state::app_config_dir().map(|dir| dir.join(PROFILES_FILE)),
+            profiles: Mutex::new(None),
+        }
+    }
+
+    pub fn list(&self) -> Result<Vec<ConnectionProfile>, String> {
+        self.with_profiles(|profiles| Ok(profiles.clone()))
+    }
+
+    pub fn get(&self, id: &str) -> Result<ConnectionProfile, String> {
+        self.with_profiles(|profiles| {
+            profiles
+                .iter()
+                .find(|profile| profile.id == id)
+                .cloned()
+                .ok_or_else(|| format!("Profile {} not found", id))
+        })
+    }
+
+    pub fn create(
+        &self,
+        name: String,
+        config: ConnectionConfig,
+        password_secret_id: Option<String>,
+        passphrase_secret_id: Option<String>,
+        jump_host_secrets: Vec<JumpHostSecrets>,
+    ) -> Result<ConnectionProfile, String> {
+        let profile = ConnectionProfile {
+            id: Uuid::new_v4().to_string(),
+            name,
+            config: config.without_secrets(),
+            password_secret_id,
+            passphrase_secret_id,
+            jump_host_secrets,
+        };
+
+        self.modify(|profiles| {
+            profiles.push(profile.clone());
+            Ok(())
+        })?;
+        Ok(profile)
+    }
+
+    pub fn update(&self, profile: ConnectionProfile) -> Result<ConnectionProfile, String> {
+        let profile = ConnectionProfile {
+            config: profile.config.without_secrets(),
+            ..profile
+        };
+
+        self.modify(|profiles| {
+            let existing = profiles
+                .iter_mut()
+                .find(|existing| existing.id == profile.id)
+                .ok_or_else(|| format!("Profile {} not found", profile.id))?;
+            *existing = profile.clone();
+            Ok(())
+        })?;
+        Ok(profile)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        self.modify(|profiles| {
+            let before = profiles.len();
+            profiles.retain(|profile| profile.id != id);
+            if profiles.len() == before {
+                return Err(format!("Profile {} not found", id));
+            }
+            Ok(())
+        })
+    }
+
+    pub fn duplicate(&self, id: &str) -> Result<ConnectionProfile, String> {
+        let mut copy = None;
+        self.modify(|profiles| {
+            let original = profiles
+                .iter()
+                .find(|profile| profile.id == id)
+                .ok_or_else(|| format!("Profile {} not found", id))?;
+            let duplicate = ConnectionProfile {
+                id: Uuid::new_v4().to_string(),
+                name: format!("{} (copy)", original.name),
+                ..original.clone()
+            };
+            profiles.push(duplicate.clone());
+            copy = Some(duplicate);
+            Ok(())
+        })?;
+        copy.ok_or_else(|| format!("Profile {} not found", id))
+    }
+
+    fn with_profiles<T>(
+        &self,
+        f: impl FnOnce(&mut Vec<ConnectionProfile>) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut guard = self.profiles.lock().unwrap();
+        if guard.is_none() {
+            *guard = Some(self.load()?);
+        }
+        f(guard.as_mut().unwrap())
+    }
+
+    /// Applies `f` and writes the result back; nothing changes on failure.
+    fn modify(&self, f: impl FnOnce(&mut Vec<ConnectionProfile>) -> Result<(), String>) -> Result<(), String> {
+        self.with_profiles(|profiles| {
+            let mut updated = profiles.clone();
+            f(&mut updated)?;
+            self.save(&updated)?;
+            *profiles = updated;
+            Ok(())
+        })
+    }
+
+    fn load(&self) -> Result<Vec<ConnectionProfile>, String> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| format!("Failed to parse profiles: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(format!("Failed to read profiles: {}", e)),
+        }
+    }
+
+    fn save(&self, profiles: &[ConnectionProfile]) -> Result<(), String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| "Could not determine config directory".to_string())?;
+
+        let contents = serde_json::to_string_pretty(profiles)
+            .map_err(|e| format!("Failed to serialize profiles: {}", e))?;
+        crate::state::write_file_atomic(path, contents.as_bytes())
+            .map_err(|e| format!("Failed to save profiles: {}", e))
+    }
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub static PROFILE_STORE: Lazy<ProfileStore> = Lazy::new(ProfileStore::new);
@@ -0,0 +1,471 @@
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use tauri::{Emitter, Window};
+
+use crate::sftp::transfer::{self, TransferControl, TransferRecord};
+use crate::sftp::{SftpClient, SftpError};
+use crate::state::connection_pool::{ConnectionDetails, CONNECTION_POOL};
+use crate::state::history::{HistoryEntry, TRANSFER_HISTORY};
+
+const DEFAULT_MAX_CONCURRENT: usize = 4;
+const DEFAULT_MAX_PER_CONNECTION: usize = 2;
+
+type PooledClient = Arc<Mutex<SftpClient>>;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Queued,
+    Paused,
+    Running,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct QueueLimits {
+    pub max_concurrent: usize,
+    pub max_per_connection: usize,
+}
+
+impl Default for QueueLimits {
+    fn default() -> Self {
+        Self {
+            max_concurrent: DEFAULT_MAX_CONCURRENT,
+            max_per_connection: DEFAULT_MAX_PER_CONNECTION,
+        }
+    }
+}
+
+#[derive(Serialize, Clone)]
+pub struct QueueEntry {
+    #[serde(flatten)]
+    pub record: TransferRecord,
+    pub priority: i32,
+    pub state: JobState,
+    pub rate_limit: Option<u64>,
+}
+
+struct Job {
+    record: TransferRecord,
+    resume: bool,
+    priority: i32,
+    state: JobState,
+    /// Set once the job has been handed to a worker. A started job keeps
+    /// its slot against the limits even while paused.
+    started: bool,
+    window: Window,
+    control: Arc<TransferControl>,
+}
+
+#[derive(Default)]
+struct QueueState {
+    jobs: Vec<Job>,
+    limits: QueueLimits,
+}
+
+/// Every transfer goes through here. Jobs run in queue order, which is by
+/// priority (higher first) and then by when they were added, unless
+/// reordered by hand. No more than `max_concurrent` run at once, and no more
+/// than `max_per_connection` on any one connection.
+pub struct TransferQueue {
+    state: Mutex<QueueState>,
+}
+
+impl Default for TransferQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TransferQueue {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(QueueState::default()),
+        }
+    }
+
+    /// Fails if a job with the same transfer ID is still queued or running,
+    /// which a retry sent twice would otherwise cause.
+    pub fn enqueue(
+        &self,
+        record: TransferRecord,
+        resume: bool,
+        priority: i32,
+        window: Window,
+    ) -> Result<String, SftpError> {
+        let transfer_id = record.transfer_id.clone();
+        let control = Arc::new(TransferControl::new(&record));
+        {
+            let mut state = self.state.lock().unwrap();
+            if find(&state.jobs, &transfer_id).is_ok() {
+                return Err(SftpError::from(format!(
+                    "Transfer {} is already in the queue",
+                    transfer_id
+                )));
+            }
+            let index = insert_position(&state.jobs, priority);
+            state.jobs.insert(
+                index,
+                Job {
+                    record,
+                    resume,
+                    priority,
+                    state: JobState::Queued,
+                    started: false,
+                    window,
+                    control,
+                },
+            );
+        }
+        self.dispatch();
+        Ok(transfer_id)
+    }
+
+    pub fn list(&self) -> Vec<QueueEntry> {
+        entries(&self.state.lock().unwrap().jobs)
+    }
+
+    pub fn limits(&self) -> QueueLimits {
+        self.state.lock().unwrap().limits
+    }
+
+    pub fn set_limits(&self, limits: QueueLimits) {
+        self.state.lock().unwrap().limits = QueueLimits {
+            max_concurrent: limits.max_concurrent.max(1),
+            max_per_connection: limits.max_per_connection.max(1),
+        };
+        self.dispatch();
+    }
+
+    /// A waiting job is held back and keeps its place in the queue; a
+    /// running one stops between chunks, keeping its open files, and lets
+    /// go of the connection until it is resumed.
+    pub fn pause(&self, transfer_id: &str) -> Result<(), SftpError> {
+        self.modify(transfer_id, |state, index| {
+            let job = &mut state.jobs[index];
+            if job.started {
+                job.control.pause();
+            }
+            job.state = JobState::Paused;
+            Ok(())
+        })
+    }
+
+    pub fn resume(&self, transfer_id: &str) -> Result<(), SftpError> {
+        self.modify(transfer_id, |state, index| {
+            let job = &mut state.jobs[index];
+            if job.state != JobState::Paused {
+                return Ok(());
+            }
+            if job.started {
+                job.control.resume();
+                job.state = JobState::Running;
+            } else {
+                job.state = JobState::Queued;
+            }
+            Ok(())
+        })?;
+        self.dispatch();
+        Ok(())
+    }
+
+    /// Moves a waiting job to where its new priority puts it. A started
+    /// job just keeps the new value.
+    pub fn set_priority(&self, transfer_id: &str, priority: i32) -> Result<(), SftpError> {
+        self.modify(transfer_id, |state, index| {
+            state.jobs[index].priority = priority;
+            if !state.jobs[index].started {
+                let job = state.jobs.remove(index);
+                let index = insert_position(&state.jobs, priority);
+                state.jobs.insert(index, job);
+            }
+            Ok(())
+        })?;
+        self.dispatch();
+        Ok(())
+    }
+
+    /// Puts a waiting job at `position` among the jobs that haven't started,
+    /// regardless of priority.
+    pub fn move_to(&self, transfer_id: &str, position: usize) -> Result<(), SftpError> {
+        self.modify(transfer_id, |state, index| {
+            if state.jobs[index].started {
+                return Err(SftpError::from(format!(
+                    "Transfer {} has already started",
+                    transfer_id
+                )));
+            }
+
+            let job = state.jobs.remove(index);
+            let index = state
+                .jobs
+                .iter()
+                .enumerate()
+                .filter(|(_, job)| !job.started)
+                .nth(position)
+                .map(|(index, _)| index)
+                .unwrap_or(state.jobs.len());
+            state.jobs.insert(index, job);
+            Ok(())
+        })?;
+        self.dispatch();
+        Ok(())
+    }
+
+    /// Changes a job's own rate limit, whether or not it has started.
+    pub fn set_rate_limit(&self, transfer_id: &str, rate_limit: Option<u64>) -> Result<(), SftpError> {
+        self.modify(transfer_id, |state, index| {
+            state.jobs[index].control.set_rate_limit(rate_limit);
+            Ok(())
+        })
+    }
+
+    /// Drops a waiting job, or asks a started one to stop.
+    pub fn cancel(&self, transfer_id: &str) -> Result<(), SftpError> {
+        let mut state = self.state.lock().unwrap();
+        let index = find(&state.jobs, transfer_id)?;
+
+        if state.jobs[index].started {
+            state.jobs[index].control.cancel();
+            return Ok(());
+        }
+
+        let job = state.jobs.remove(index);
+        transfer::emit_cancelled(&job.window, &job.record);
+        record_cancelled(&job.record);
+        emit_queue(&state, &job.window);
+        Ok(())
+    }
+
+    /// Drops every job that hasn't started, optionally only for one
+    /// connection, and returns how many were removed.
+    pub fn clear(&self, connection_id: Option<&str>) -> usize {
+        let mut state = self.state.lock().unwrap();
+        let (cleared, kept): (Vec<Job>, Vec<Job>) = std::mem::take(&mut state.jobs)
+            .into_iter()
+            .partition(|job| {
+                !job.started && connection_id.is_none_or(|id| job.record.uses_connection(id))
+            });
+        state.jobs = kept;
+
+        for job in &cleared {
+            transfer::emit_cancelled(&job.window, &job.record);
+            record_cancelled(&job.record);
+        }
+        if let Some(job) = cleared.last() {
+            emit_queue(&state, &job.window);
+        }
+        cleared.len()
+    }
+
+    fn modify(
+        &self,
+        transfer_id: &str,
+        f: impl FnOnce(&mut QueueState, usize) -> Result<(), SftpError>,
+    ) -> Result<(), SftpError> {
+        let mut state = self.state.lock().unwrap();
+        let index = find(&state.jobs, transfer_id)?;
+        let window = state.jobs[index].window.clone();
+        f(&mut state, index)?;
+        emit_queue(&state, &window);
+        Ok(())
+    }
+
+    /// Starts as many queued jobs as the limits allow, in queue order.
+    fn dispatch(&self) {
+        let mut state = self.state.lock().unwrap();
+        let mut window = None;
+
+        loop {
+            let running = state.jobs.iter().filter(|job| job.started).count();
+            if running >= state.limits.max_concurrent {
+                break;
+            }
+
+            let has_room = |connection_id: &str| {
+                running_on(&state.jobs, connection_id) < state.limits.max_per_connection
+            };
+            let next = state.jobs.iter().position(|job| {
+                job.state == JobState::Queued
+                    && !job.started
+                    && has_room(&job.record.connection_id)
+                    && job.record.target_connection_id.as_deref().is_none_or(has_room)
+            });
+            let Some(index) = next else {
+                break;
+            };
+
+            let job = &mut state.jobs[index];
+            window = Some(job.window.clone());
+            let (client_arc, target_arc) = match job_clients(&job.record) {
+                Ok(clients) => clients,
+                Err(error) => {
+                    let job = state.jobs.remove(index);
+                    transfer::emit_error(&job.window, &job.record, &error);
+                    record_history(&job.record, None, SystemTime::now(), 0, &Err(error));
+                    continue;
+                }
+            };
+
+            job.state = JobState::Running;
+            job.started = true;
+            let record = job.record.clone();
+            let resume = job.resume;
+            let job_window = job.window.clone();
+            let control = job.control.clone();
+            let connection = CONNECTION_POOL.details(&record.connection_id);
+
+            tokio::task::spawn_blocking(move || {
+                let started_at = SystemTime::now();
+                let result =
+                    run_job(&record, resume, &job_window, client_arc, target_arc, &control);
+                let bytes = control.bytes_transferred();
+                record_history(&record, connection, started_at, bytes, &result);
+                // Out of the queue before anyone hears of the failure, so a
+                // retry sent straight back isn't refused as a duplicate.
+                TRANSFER_QUEUE.finish(&control, &job_window);
+                if let Err(e) = &result {
+                    transfer::emit_failure(&job_window, &control.resumable(&record), e);
+                }
+            });
+        }
+
+        if let Some(window) = window {
+            emit_queue(&state, &window);
+        }
+    }
+
+    /// Removes the job that owns `control`, and only that one.
+    fn finish(&self, control: &Arc<TransferControl>, window: &Window) {
+        {
+            let mut state = self.state.lock().unwrap();
+            state.jobs.retain(|job| !Arc::ptr_eq(&job.control, control));
+            emit_queue(&state, window);
+        }
+        self.dispatch();
+    }
+}
+
+/// Runs a job, trying again as its retry policy allows. A connection that
+/// died is re-established through the pool before the next attempt, and
+/// every attempt after the first resumes partial files.
+fn run_job(
+    record: &TransferRecord,
+    resume: bool,
+    window: &Window,
+    mut client_arc: PooledClient,
+    mut target_arc: Option<PooledClient>,
+    control: &TransferControl,
+) -> Result<(), SftpError> {
+    let policy = &record.options.retry;
+    let mut attempt = 1;
+
+    loop {
+        let resume = resume || attempt > 1;
+        let target = target_arc.as_deref();
+        let result = transfer::execute_transfer(record, resume, window, &client_arc, target, control);
+        let error = match result {
+            Ok(()) => return Ok(()),
+            Err(e) => e,
+        };
+
+        if !policy.should_retry(&error, attempt) {
+            return Err(error);
+        }
+
+        let delay = policy.delay(attempt);
+        attempt += 1;
+        transfer::emit_retry(window, record, attempt, delay, &error);
+        if !control.wait(delay) {
+            return Err(SftpError::cancelled(record.direction.as_str(), &record.remote_path));
+        }
+
+        if error.is_connection_error() || matches!(error, SftpError::ConnectionNotFound { .. }) {
+            client_arc = CONNECTION_POOL.ensure_alive(&record.connection_id)?;
+            if let Some(target_id) = &record.target_connection_id {
+                target_arc = Some(CONNECTION_POOL.ensure_alive(target_id)?);
+            }
+        }
+    }
+}
+
+/// The pooled clients a job runs on: its own and, for copies, the target's.
+fn job_clients(record: &TransferRecord) -> Result<(PooledClient, Option<PooledClient>), SftpError> {
+    let client = |connection_id: &str| {
+        CONNECTION_POOL
+            .get(connection_id)
+            .ok_or_else(|| SftpError::ConnectionNotFound {
+                connection_id: connection_id.to_string(),
+            })
+    };
+    let target = record.target_connection_id.as_deref().map(client).transpose()?;
+    Ok((client(&record.connection_id)?, target))
+}
+
+/// A history that can't be written shouldn't fail the transfer itself.
+fn record_history(
+    record: &TransferRecord,
+    connection: Option<ConnectionDetails>,
+    started_at: SystemTime,
+    bytes_transferred: u64,
+    result: &Result<(), SftpError>,
+) {
+    let entry = HistoryEntry::new(record, connection, started_at, bytes_transferred, result);
+    TRANSFER_HISTORY.record(&entry).ok();
+}
+
+/// For jobs dropped from the queue before they started.
+fn record_cancelled(record: &TransferRecord) {
+    let connection = CONNECTION_POOL.details(&record.connection_id);
+    let error = SftpError::cancelled(record.direction.as_str(), &record.remote_path);
+    record_history(record, connection, SystemTime::now(), 0, &Err(error));
+}
+
+/// After every job of equal or higher priority, so equal priorities stay
+/// first come, first served.
+fn insert_position(jobs: &[Job], priority: i32) -> usize {
+    jobs.iter()
+        .rposition(|job| job.started || job.priority >= priority)
+        .map_or(0, |index| index + 1)
+}
+
+fn running_on(jobs: &[Job], connection_id: &str) -> usize {
+    jobs.iter()
+        .filter(|job| job.started && job.record.uses_connection(connection_id))
+        .count()
+}
+
+fn find(jobs: &[Job], transfer_id: &str) -> Result<usize, SftpError> {
+    jobs.iter()
+        .position(|job| job.record.transfer_id == transfer_id)
+        .ok_or_else(|| SftpError::TransferNotFound {
+            transfer_id: transfer_id.to_string(),
+        })
+}
+
+fn entries(jobs: &[Job]) -> Vec<QueueEntry> {
+    jobs.iter()
+        .map(|job| QueueEntry {
+            record: job.record.clone(),
+            priority: job.priority,
+            state: job.state,
+            rate_limit: job.control.rate_limit(),
+        })
+        .collect()
+}
+
+fn emit_queue(state: &QueueState, window: &Window) {
+    window
+        .emit(
+            "transfer_queue",
+            serde_json::json!({
+                "jobs": entries(&state.jobs),
+                "limits": state.limits
+            }),
+        )
+        .ok();
+}
+
+pub static TRANSFER_QUEUE: Lazy<TransferQueue> = Lazy::new(TransferQueue::new);
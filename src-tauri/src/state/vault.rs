@@ -0,0 +1,541 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use zeroize::Zeroizing;
+
+use crate::sftp::Secret;
+
+const VAULT_FILE: &str = "vault.json";
+const VAULT_VERSION: u32 = 1;
+const CHECK_AAD: &[u8] = b"vault-check";
+const CHECK_PLAINTEXT: &[u8] = b"christoph-vault";
+const DEFAULT_AUTO_LOCK_SECS: u64 = 15 * 60;
+const AUTO_LOCK_POLL: Duration = Duration::from_secs(5);
+
+// OWASP's recommended Argon2id baseline.
+const ARGON2_M_COST: u32 = 19 * 1024;
+const ARGON2_T_COST: u32 = 2;
+const ARGON2_P_COST: u32 = 1;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    salt: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct VaultEntry {
+    label: String,
+    #[serde(flatten)]
+    sealed: Sealed,
+}
+
+/// On-disk layout. Only the KDF parameters, labels and settings are in the
+/// clear; every secret is sealed with a key derived from the master password.
+#[derive(Serialize, Deserialize, Clone)]
+struct VaultFile {
+    version: u32,
+    kdf: KdfParams,
+    check: Sealed,
+    auto_lock_secs: u64,
+    entries: BTreeMap<String, VaultEntry>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct VaultStatus {
+    pub exists: bool,
+    pub unlocked: bool,
+    pub auto_lock_secs: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct VaultEntryInfo {
+    pub id: String,
+    pub label: String,
+}
+
+struct UnlockedVault {
+    key: Zeroizing<[u8; 32]>,
+    last_used: Instant,
+}
+
+struct VaultState {
+    file: Option<VaultFile>,
+    unlocked: Option<UnlockedVault>,
+    loaded: bool,
+}
+
+/// Local credential vault. Secrets are encrypted with ChaCha20-Poly1305 under
+/// an Argon2id-derived key that only lives in memory while unlocked, and is
+/// dropped again after `auto_lock_secs` without use.
+pub struct Vault {
+    path: Option<PathBuf>,
+    state: Mutex<VaultState>,
+}
+
+impl Vault {
+    pub fn new() -> Self {
+        Self {
+            path: crate::state::app_config_dir().map(|dir| dir.join(VAULT_FILE)),
+            state: Mutex::new(VaultState {
+                file: None,
+                unlocked: None,
+                loaded: false,
+            }),
+        }
+    }
+
+    pub fn status(&self) -> Result<VaultStatus, String> {
+        self.with_state(|state| {
+            Ok(VaultStatus {
+                exists: state.file.is_some(),
+                unlocked: state.unlocked.is_some(),
+                auto_lock_secs: state
+                    .file
+                    .as_ref()
+                    .map_or(DEFAULT_AUTO_LOCK_SECS, |file| file.auto_lock_secs),
+            })
+        })
+    }
+
+    pub fn create(&self, master_password: &str) -> Result<(), String> {
+        self.with_state(|state| {
+            if state.file.is_some() {
+                return Err("Vault already exists".to_string());
+            }
+
+            let mut salt = [0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            let kdf = KdfParams {
+                m_cost: ARGON2_M_COST,
+                t_cost: ARGON2_T_COST,
+                p_cost: ARGON2_P_COST,
+                salt: STANDARD.encode(salt),
+            };
+            let key = derive_key(master_password, &kdf)?;
+
+            let file = VaultFile {
+                version: VAULT_VERSION,
+                check: seal(&key, CHECK_AAD, CHECK_PLAINTEXT)?,
+                kdf,
+                auto_lock_secs: DEFAULT_AUTO_LOCK_SECS,
+                entries: BTreeMap::new(),
+            };
+            self.save(&file)?;
+
+            state.file = Some(file);
+            state.unlocked = Some(UnlockedVault {
+                key,
+                last_used: Instant::now(),
+            });
+            Ok(())
+        })?;
+
+        start_auto_lock_timer();
+        Ok(())
+    }
+
+    pub fn unlock(&self, master_password: &str) -> Result<(), String> {
+        self.with_state(|state| {
+            let file = state
+                .file
+                .as_ref()
+                .ok_or_else(|| "Vault has not been created".to_string())?;
+
+            let key = derive_key(master_password, &file.kdf)?;
+            open(&key, CHECK_AAD, &file.check)
+                .map_err(|_| "Incorrect master password".to_string())?;
+
+            state.unlocked = Some(UnlockedVault {
+                key,
+                last_used: Instant::now(),
+            });
+            Ok(())
+        })?;
+
+        start_auto_lock_timer();
+        Ok(())
+    }
+
+    pub fn lock(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.unlocked = None;
+        }
+    }
+
+    pub fn set_auto_lock(&self, seconds: u64) -> Result<(), String> {
+        self.modify(|file, _| {
+            file.auto_lock_secs = seconds;
+            Ok(())
+        })
+    }
+
+    pub fn list(&self) -> Result<Vec<VaultEntryInfo>, String> {
+        self.with_state(|state| {
+            Ok(state
+                .file
+                .iter()
+                .flat_map(|file| &file.entries)
+                .map(|(id, entry)| VaultEntryInfo {
+                    id: id.clone(),
+                    label: entry.label.clone(),
+                })
+                .collect())
+        })
+    }
+
+    pub fn store(&self, label: String, secret: &str) -> Result<String, String> {
+        let id = Uuid::new_v4().to_string();
+        self.modify(|file, key| {
+            let sealed = seal(key, id.as_bytes(), secret.as_bytes())?;
+            file.entries.insert(id.clone(), VaultEntry { label, sealed });
+            Ok(())
+        })?;
+        Ok(id)
+    }
+
+    pub fn update(&self, id: &str, secret: &str) -> Result<(), String> {
+        self.modify(|file, key| {
+            let entry = file
+                .entries
+                .get_mut(id)
+                .ok_or_else(|| format!("Vault entry {} not found", id))?;
+            entry.sealed = seal(key, id.as_bytes(), secret.as_bytes())?;
+            Ok(())
+        })
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), String> {
+        self.modify(|file, _| {
+            file.entries
+                .remove(id)
+                .map(|_| ())
+                .ok_or_else(|| format!("Vault entry {} not found", id))
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Result<Secret, String> {
+        self.with_unlocked(|file, key| {
+            let entry = file
+                .entries
+                .get(id)
+                .ok_or_else(|| format!("Vault entry {} not found", id))?;
+            let plaintext = open(key, id.as_bytes(), &entry.sealed)?;
+            std::str::from_utf8(&plaintext)
+                .map(|secret| Secret::from(secret.to_string()))
+                .map_err(|_| "Vault entry is not valid UTF-8".to_string())
+        })
+    }
+
+    /// Locks the vault if it has been idle for longer than its timeout.
+    fn lock_if_idle(&self) {
+        if let Ok(mut state) = self.state.lock() {
+            expire_if_idle(&mut state);
+        }
+    }
+
+    fn with_state<T>(
+        &self,
+        f: impl FnOnce(&mut VaultState) -> Result<T, String>,
+    ) -> Result<T, String> {
+        let mut state = self.state.lock().unwrap();
+        if !state.loaded {
+            state.file = self.load()?;
+            state.loaded = true;
+        }
+        expire_if_idle(&mut state);
+        f(&mut state)
+    }
+
+    fn with_unlocked<T>(
+        &self,
+        f: impl FnOnce(&mut VaultFile, &[u8; 32]) -> Result<T, String>,
+    ) -> Result<T, String> {
+        self.with_state(|state| {
+            let (Some(file), Some(unlocked)) = (state.file.as_mut(), state.unlocked.as_mut()) else {
+                return Err("Vault is locked".to_string());
+            };
+            unlocked.last_used = Instant::now();
+            f(file, &unlocked.key)
+        })
+    }
+
+    /// Applies `f` to a copy of the vault and persists it; nothing changes
+    /// in memory unless the write succeeds.
+    fn modify(
+        &self,
+        f: impl FnOnce(&mut VaultFile, &[u8; 32]) -> Result<(), String>,
+    ) -> Result<(), String> {
+        self.with_unlocked(|file, key| {
+            let mut updated = file.clone();
+            f(&mut updated, key)?;
+            self.save(&updated)?;
+            *file = updated;
+            Ok(())
+        })
+    }
+
+    fn load(&self) -> Result<Option<VaultFile>, String> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+
+        match fs::read_to_string(path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map(Some)
+                .map_err(|e| format!("Failed to parse vault: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("Failed to read vault: {}", e)),
+        }
+    }
+
+    fn save(&self, file: &VaultFile) -> Result<(), String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| "Could not determine config directory".to_string())?;
+
+        let contents = serde_json::to_string_pretty(file)
+            .map_err(|e| format!("Failed to serialize vault: {}", e))?;
+        crate::state::write_file_atomic(path, contents.as_bytes())
+            .map_err(|e| format!("Failed to save vault: {}", e))
+    }
+}
+
+impl Default for Vault {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn expire_if_idle(state: &mut VaultState) {
+    let timeout = state.file.as_ref().map_or(0, |file| file.auto_lock_secs);
+    if timeout == 0 {
+        return;
+    }
+
+    let idle = state
+        .unlocked
+        .as_ref()
+        .is_some_and(|unlocked| unlocked.last_used.elapsed() >= Duration::from_secs(timeout));
+    if idle {
+        state.unlocked = None;
+    }
+}
+
+/// Polls in the background so an idle vault gets locked even when nothing
+/// touches it. Only one timer runs at a time.
+fn start_auto_lock_timer() {
+    static TIMER_STARTED: Lazy<Mutex<bool>> = Lazy::new(|| Mutex::new(false));
+
+    let mut started = TIMER_STARTED.lock().unwrap();
+    if *started {
+        return;
+    }
+    *started = true;
+
+    thread::spawn(|| loop {
+        thread::sleep(AUTO_LOCK_POLL);
+        VAULT.lock_if_idle();
+    });
+}
+
+fn derive_key(master_password: &str, kdf: &KdfParams) -> Result<Zeroizing<[u8; 32]>, String> {
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| format!("Invalid vault key parameters: {}", e))?;
+    let salt = STANDARD
+        .decode(&kdf.salt)
+        .map_err(|e| format!("Invalid vault salt: {}", e))?;
+
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(master_password.as_bytes(), &salt, key.as_mut())
+        .map_err(|e| format!("Failed to derive vault key: {}", e))?;
+    Ok(key)
+}
+
+/// Encrypts `plaintext`, binding it to `aad` (the entry ID) so ciphertexts
+/// can't be swapped between entries.
+fn seal(key: &[u8; 32], aad: &[u8], plaintext: &[u8]) -> Result<Sealed, String> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, Payload { msg: plaintext, aad })
+        .map_err(|_| "Failed to encrypt vault entry".to_string())?;
+
+    Ok(Sealed {
+        nonce: STANDARD.encode(nonce),
+        ciphertext: STANDARD.encode(ciphertext),
+    })
+}
+
+fn open(key: &[u8; 32], aad: &[u8], sealed: &Sealed) -> Result<Zeroizing<Vec<u8>>, String> {
+    let nonce = STANDARD
+        .decode(&sealed.nonce)
+        .map_err(|_| "Corrupt vault entry".to_string())?;
+    let ciphertext = STANDARD
+        .decode(&sealed.ciphertext)
+        .map_err(|_| "Corrupt vault entry".to_string())?;
+    if nonce.len() != 12 {
+        return Err("Corrupt vault entry".to_string());
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &ciphertext, aad })
+        .map(Zeroizing::new)
+        .map_err(|_| "Failed to decrypt vault entry".to_string())
+}
+
+pub static VAULT: Lazy<Vault> = Lazy::new(Vault::new);
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    fn vault(name: &str) -> (Vault, PathBuf) {
+        let path = std::env::temp_dir()
+            .join(format!("christoph-vault-{}-{}.json", name, std::process::id()));
+        fs::remove_file(&path).ok();
+        (reopen(&path), path)
+    }
+
+    /// The vault at `path` as a fresh process would see it.
+    fn reopen(path: &Path) -> Vault {
+        Vault {
+            path: Some(path.to_path_buf()),
+            state: Mutex::new(VaultState {
+                file: None,
+                unlocked: None,
+                loaded: false,
+            }),
+        }
+    }
+
+    #[test]
+    fn seal_and_open_round_trip() {
+        let key = [1u8; 32];
+        let sealed = seal(&key, b"entry", b"hunter2").unwrap();
+        assert_eq!(open(&key, b"entry", &sealed).unwrap().as_slice(), b"hunter2");
+        assert!(open(&[2u8; 32], b"entry", &sealed).is_err());
+
+        // A fresh nonce every time, so equal secrets don't look equal.
+        let again = seal(&key, b"entry", b"hunter2").unwrap();
+        assert_ne!(again.nonce, sealed.nonce);
+        assert_ne!(again.ciphertext, sealed.ciphertext);
+    }
+
+    #[test]
+    fn secrets_survive_a_restart_and_need_the_master_password() {
+        let (vault, path) = vault("round-trip");
+        vault.create("correct horse").unwrap();
+        let id = vault.store("server".to_string(), "hunter2").unwrap();
+        assert_eq!(vault.get(&id).unwrap().expose(), "hunter2");
+
+        let reopened = reopen(&path);
+        assert_eq!(reopened.get(&id).unwrap_err(), "Vault is locked");
+        assert_eq!(reopened.unlock("battery staple").unwrap_err(), "Incorrect master password");
+        assert!(reopened.get(&id).is_err());
+
+        reopened.unlock("correct horse").unwrap();
+        assert_eq!(reopened.get(&id).unwrap().expose(), "hunter2");
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn ciphertexts_are_bound_to_their_entry() {
+        let (vault, path) = vault("aad");
+        vault.create("master").unwrap();
+        let first = vault.store("first".to_string(), "one").unwrap();
+        let second = vault.store("second".to_string(), "two").unwrap();
+
+        {
+            let mut state = vault.state.lock().unwrap();
+            let entries = &mut state.file.as_mut().unwrap().entries;
+            let moved = entries[&first].sealed.clone();
+            entries.get_mut(&second).unwrap().sealed = moved;
+        }
+        assert_eq!(vault.get(&first).unwrap().expose(), "one");
+        assert_eq!(vault.get(&second).unwrap_err(), "Failed to decrypt vault entry");
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn updating_a_secret_seals_it_again() {
+        let (vault, path) = vault("update");
+        vault.create("master").unwrap();
+        let id = vault.store("server".to_string(), "old password").unwrap();
+        let before = vault.state.lock().unwrap().file.as_ref().unwrap().entries[&id].clone();
+
+        vault.update(&id, "new password").unwrap();
+        let after = vault.state.lock().unwrap().file.as_ref().unwrap().entries[&id].clone();
+        assert_ne!(after.sealed.nonce, before.sealed.nonce);
+        assert_eq!(after.label, "server");
+        assert_eq!(vault.get(&id).unwrap().expose(), "new password");
+
+        let reopened = reopen(&path);
+        reopened.unlock("master").unwrap();
+        assert_eq!(reopened.get(&id).unwrap().expose(), "new password");
+        assert!(vault.update("missing", "x").is_err());
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn idle_vaults_lock_and_drop_their_key() {
+        let (vault, path) = vault("idle");
+        vault.create("master").unwrap();
+        let id = vault.store("server".to_string(), "secret").unwrap();
+        vault.set_auto_lock(60).unwrap();
+
+        {
+            let mut state = vault.state.lock().unwrap();
+            state.unlocked.as_mut().unwrap().last_used -= Duration::from_secs(30);
+            expire_if_idle(&mut state);
+            assert!(state.unlocked.is_some());
+
+            state.unlocked.as_mut().unwrap().last_used -= Duration::from_secs(31);
+            expire_if_idle(&mut state);
+            assert!(state.unlocked.is_none());
+        }
+        assert_eq!(vault.get(&id).unwrap_err(), "Vault is locked");
+        assert!(!vault.status().unwrap().unlocked);
+        fs::remove_file(path).ok();
+    }
+
+    #[test]
+    fn auto_lock_can_be_disabled() {
+        let (vault, path) = vault("no-auto-lock");
+        vault.create("master").unwrap();
+        vault.set_auto_lock(0).unwrap();
+
+        let mut state = vault.state.lock().unwrap();
+        state.unlocked.as_mut().unwrap().last_used -= Duration::from_secs(24 * 60 * 60);
+        expire_if_idle(&mut state);
+        assert!(state.unlocked.is_some());
+        drop(state);
+        fs::remove_file(path).ok();
+    }
+}
@@ -0,0 +1,69 @@
+//! Connects through a jump host to a second SSH server. Needs two servers,
+//! so it's ignored by default; point it at them and run
+//! `cargo test --test jump_host -- --ignored`:
+//!
+//! - `SFTP_TEST_JUMP`: the bastion as `user@host:port`
+//! - `SFTP_TEST_TARGET`: the server behind it as `user@host:port`, with the
+//!   host as the bastion sees it
+//! - `SFTP_TEST_JUMP_PASSWORD`, `SFTP_TEST_TARGET_PASSWORD`
+//!
+//! Host keys are trusted on first use into a throwaway known_hosts file.
+
+use std::env;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use christoph_lib::sftp::{known_hosts, ConnectionConfig, Secret, SftpClient, SftpError};
+
+fn hop(name: &str, known_hosts_path: &str) -> ConnectionConfig {
+    let address = env::var(format!("SFTP_TEST_{}", name))
+        .unwrap_or_else(|_| panic!("SFTP_TEST_{} is not set", name));
+    let (username, host_port) = address
+        .split_once('@')
+        .unwrap_or_else(|| panic!("SFTP_TEST_{} should be user@host:port", name));
+    let (host, port) = host_port.rsplit_once(':').unwrap_or((host_port, "22"));
+
+    ConnectionConfig {
+        host: host.to_string(),
+        port: port.parse().expect("invalid port"),
+        username: username.to_string(),
+        password: env::var(format!("SFTP_TEST_{}_PASSWORD", name)).ok().map(Secret::from),
+        known_hosts_path: Some(known_hosts_path.to_string()),
+        ..Default::default()
+    }
+}
+
+fn connect(config: &ConnectionConfig, known_hosts_path: &Path) -> SftpClient {
+    loop {
+        match SftpClient::connect(config.clone(), None) {
+            Err(SftpError::UnknownHost(info)) => {
+                let (host, port) = (info.host.as_str(), info.port);
+                known_hosts::trust_host_key(known_hosts_path, host, port, &info.fingerprint)
+                    .expect("failed to trust host key");
+            }
+            other => return other.expect("failed to connect"),
+        }
+    }
+}
+
+#[test]
+#[ignore = "needs two SSH servers, see the module docs"]
+fn connects_through_a_jump_host() {
+    let known_hosts_path =
+        env::temp_dir().join(format!("christoph-jump-host-{}", std::process::id()));
+    let path = known_hosts_path.to_string_lossy().to_string();
+
+    let mut config = hop("TARGET", &path);
+    config.jump_hosts = vec![hop("JUMP", &path)];
+
+    let client = connect(&config, &known_hosts_path);
+    client.list_directory(".").expect("listing through the tunnel failed");
+
+    // Long enough for the tunnel's idle back-off to reach its cap.
+    thread::sleep(Duration::from_millis(500));
+    client.list_directory(".").expect("tunnel stopped working after idling");
+
+    drop(client);
+    std::fs::remove_file(&known_hosts_path).ok();
+}